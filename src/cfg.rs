@@ -0,0 +1,122 @@
+//! code for finding the code that is disabled by `#[cfg(...)]` in every compilation of a file
+//! the embedded compiler reports the cfg options of each compilation, and the predicates of the
+//! documents are evaluated against them, since the compiler drops disabled code before analysis
+
+use std::collections::{HashMap, HashSet};
+
+use tower_lsp::lsp_types::Url;
+
+use crate::syntax::CfgRegion;
+
+/// the cfg options that a compilation is configured with, such as `unix` or `feature = "gpu"`
+pub type CfgOptions = HashSet<(String, Option<String>)>;
+
+/// the predicate of a `#[cfg(...)]` attribute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cfg {
+    /// a name, optionally with a value
+    Option(String, Option<String>),
+    All(Vec<Cfg>),
+    Any(Vec<Cfg>),
+    Not(Box<Cfg>),
+}
+
+impl Cfg {
+    pub fn eval(&self, options: &CfgOptions) -> bool {
+        match self {
+            Self::Option(name, value) => options.contains(&(name.clone(), value.clone())),
+            Self::All(cfgs) => cfgs.iter().all(|cfg| cfg.eval(options)),
+            Self::Any(cfgs) => cfgs.iter().any(|cfg| cfg.eval(options)),
+            Self::Not(cfg) => !cfg.eval(options),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CfgTable {
+    /// the options of every compilation that included the file
+    pub inner: HashMap<Url, Vec<CfgOptions>>,
+}
+
+impl CfgTable {
+    pub fn merge_replace(&mut self, other: Self) {
+        for (url, options) in other.inner {
+            self.inner.entry(url).insert_entry(options);
+        }
+    }
+
+    /// drop the results of the files that `keep` rejects, such as files that were deleted
+    pub fn retain_files(&mut self, keep: impl Fn(&Url) -> bool) {
+        self.inner.retain(|url, _| keep(url));
+    }
+
+    /// whether the predicate is false in every compilation that included the file
+    /// files that were not compiled have no inactive code, since their options are unknown
+    pub fn inactive(&self, url: &Url, cfg: &Cfg) -> bool {
+        self.inner
+            .get(url)
+            .is_some_and(|options| options.iter().all(|options| !cfg.eval(options)))
+    }
+
+    /// the regions of the file that are inactive, without the regions inside them
+    pub fn inactive_regions<'a>(&self, url: &Url, regions: &'a [CfgRegion]) -> Vec<&'a CfgRegion> {
+        let mut inactive: Vec<&CfgRegion> = Vec::new();
+        for region in regions {
+            // the regions are sorted by their start, so the regions inside an inactive region
+            // directly follow it
+            if inactive
+                .last()
+                .is_some_and(|last| region.range.end <= last.range.end)
+            {
+                continue;
+            }
+            if self.inactive(url, &region.cfg) {
+                inactive.push(region);
+            }
+        }
+        inactive
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(options: &[(&str, Option<&str>)]) -> CfgOptions {
+        options
+            .iter()
+            .map(|(name, value)| ((*name).to_owned(), value.map(str::to_owned)))
+            .collect()
+    }
+
+    fn option(name: &str, value: Option<&str>) -> Cfg {
+        Cfg::Option(name.to_owned(), value.map(str::to_owned))
+    }
+
+    #[test]
+    fn inactive() {
+        let url = Url::parse("file:///src/lib.rs").expect("valid url");
+        let lib = options(&[("unix", None), ("feature", Some("std"))]);
+        let test = options(&[("unix", None), ("feature", Some("std")), ("test", None)]);
+        let mut table = CfgTable::default();
+        table.inner.insert(url.clone(), Vec::from([lib, test]));
+
+        let gpu = option("feature", Some("gpu"));
+        assert!(table.inactive(&url, &gpu));
+        assert!(!table.inactive(&url, &Cfg::Not(Box::new(gpu.clone()))));
+        assert!(table.inactive(&url, &option("windows", None)));
+        // enabled in the test build only
+        assert!(!table.inactive(&url, &option("test", None)));
+        assert!(table.inactive(
+            &url,
+            &Cfg::All(Vec::from([option("test", None), gpu.clone()]))
+        ));
+        assert!(!table.inactive(
+            &url,
+            &Cfg::Any(Vec::from([option("windows", None), option("unix", None)]))
+        ));
+
+        let unknown = Url::parse("file:///build.rs").expect("valid url");
+        assert!(!table.inactive(&unknown, &gpu));
+    }
+}
@@ -0,0 +1,275 @@
+//! settings of the server, sent by the client as the `initializationOptions` of the initialize
+//! request and again whenever they change
+//! every field is optional, missing fields keep their default value
+
+use std::env;
+
+use serde::Deserialize;
+use tower_lsp::lsp_types::DiagnosticSeverity;
+
+/// the section of the client settings that belongs to the server
+pub const SECTION: &str = "minira";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[expect(clippy::struct_excessive_bools)]
+pub struct Config {
+    /// check the workspace when a document is saved
+    /// - the check compiles the files on disk, so there is no mode that checks on every change: it
+    ///   would check the last saved text again until the document is saved
+    /// - clients that save automatically after a delay are checked as they are edited, and unsaved
+    ///   changes are covered by the syntax errors
+    pub check_on_save: bool,
+    /// report the syntax errors of a document on every change, before it is saved
+    pub syntax_errors_on_change: bool,
+    /// run clippy instead of cargo check for diagnostics, which adds its lints
+    pub clippy: bool,
+    /// check tests, benches and examples along with libraries and binaries, like
+    /// `cargo check --all-targets`
+    pub all_targets: bool,
+    /// index the items of dependencies so that workspace symbol queries ending with `*` find them,
+    /// which records every item of every dependency of the check
+    pub dependency_symbols: bool,
+    /// features and target of the check that reports diagnostics
+    #[serde(flatten)]
+    pub cargo: CargoOptions,
+    /// more features and targets checked by the embedded compiler, whose results are merged with
+    /// those of the main check so that code behind any of their cfgs can be hovered
+    pub configurations: Vec<CargoOptions>,
+    /// extra arguments passed to rustc, such as `--cfg` or lint levels
+    pub extra_args: Vec<String>,
+    pub hints: Hints,
+}
+
+/// the features and target that the workspace is checked with
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CargoOptions {
+    /// cargo features to enable
+    pub features: Vec<String>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+    /// target triple to check for instead of the host, which needs the standard library of the
+    /// target to be installed
+    pub target: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            check_on_save: true,
+            syntax_errors_on_change: true,
+            clippy: true,
+            all_targets: true,
+            dependency_symbols: false,
+            cargo: CargoOptions::default(),
+            configurations: Vec::new(),
+            extra_args: Vec::new(),
+            hints: Hints::default(),
+        }
+    }
+}
+
+/// which of the messages attached to compiler diagnostics are published on their own
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Hints {
+    /// `help` messages, which carry the quick fixes, published as hints
+    pub help: bool,
+    /// `note` messages, published as information
+    pub notes: bool,
+    /// code disabled by `#[cfg(...)]` in every check, published as unnecessary hints
+    pub inactive: bool,
+}
+
+impl Default for Hints {
+    fn default() -> Self {
+        Self {
+            help: true,
+            notes: true,
+            inactive: true,
+        }
+    }
+}
+
+impl Hints {
+    /// whether diagnostics of the severity are published
+    pub fn shows(&self, severity: Option<DiagnosticSeverity>) -> bool {
+        match severity {
+            Some(DiagnosticSeverity::HINT) => self.help,
+            Some(DiagnosticSeverity::INFORMATION) => self.notes,
+            _ => true,
+        }
+    }
+}
+
+impl CargoOptions {
+    /// arguments of cargo commands that select the features and the target
+    pub fn cargo_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if !self.features.is_empty() {
+            args.extend(["--features".to_owned(), self.features.join(",")]);
+        }
+        if self.all_features {
+            args.push("--all-features".to_owned());
+        }
+        if self.no_default_features {
+            args.push("--no-default-features".to_owned());
+        }
+        if let Some(target) = &self.target {
+            args.extend(["--target".to_owned(), target.clone()]);
+        }
+        args
+    }
+
+    /// a short description of the configuration for messages, such as `--features gpu`
+    pub fn describe(&self) -> String {
+        let args = self.cargo_args();
+        if args.is_empty() {
+            "the default configuration".to_owned()
+        } else {
+            format!("`{}`", args.join(" "))
+        }
+    }
+}
+
+impl Config {
+    /// - settings that are `null` keep every default
+    /// - the settings may be nested in the section of the server, as clients send them
+    pub fn parse(settings: serde_json::Value) -> serde_json::Result<Self> {
+        match settings {
+            serde_json::Value::Null => Ok(Self::default()),
+            serde_json::Value::Object(mut settings) if settings.contains_key(SECTION) => {
+                Self::parse(
+                    settings
+                        .remove(SECTION)
+                        .expect("section is in the settings"),
+                )
+            }
+            settings => serde_json::from_value(settings),
+        }
+    }
+
+    /// whether the settings that change the results of a check differ
+    pub fn check_changed(&self, other: &Self) -> bool {
+        self.clippy != other.clippy
+            || self.all_targets != other.all_targets
+            || self.dependency_symbols != other.dependency_symbols
+            || self.cargo != other.cargo
+            || self.configurations != other.configurations
+            || self.extra_args != other.extra_args
+    }
+
+    /// every configuration checked by the embedded compiler, starting with the main one
+    pub fn all_configurations(&self) -> Vec<CargoOptions> {
+        let mut configurations = Vec::from([self.cargo.clone()]);
+        for configuration in &self.configurations {
+            if !configurations.contains(configuration) {
+                configurations.push(configuration.clone());
+            }
+        }
+        configurations
+    }
+
+    /// the value of `CARGO_ENCODED_RUSTFLAGS` that adds the extra arguments to the flags set in the
+    /// environment, which cargo would otherwise ignore
+    /// the arguments are separated by `0x1f` so that they may contain spaces
+    pub fn encoded_rustflags(&self) -> Option<String> {
+        if self.extra_args.is_empty() {
+            return None;
+        }
+
+        let mut flags = match env::var("CARGO_ENCODED_RUSTFLAGS") {
+            Ok(flags) if !flags.is_empty() => flags.split('\x1f').map(str::to_owned).collect(),
+            _ => env::var("RUSTFLAGS").map_or_else(
+                |_| Vec::new(),
+                |flags| flags.split_whitespace().map(str::to_owned).collect(),
+            ),
+        };
+        flags.extend(self.extra_args.iter().cloned());
+        Some(flags.join("\x1f"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn defaults() {
+        assert_eq!(
+            Config::parse(serde_json::Value::Null).expect("valid settings"),
+            Config::default()
+        );
+        assert_eq!(
+            Config::parse(json!({})).expect("valid settings"),
+            Config::default()
+        );
+
+        let config = Config::parse(json!({
+            "checkOnSave": false,
+            "hints": { "notes": false },
+        }))
+        .expect("valid settings");
+        assert!(!config.check_on_save);
+        assert!(config.clippy);
+        assert!(!config.dependency_symbols);
+        assert!(config.hints.help);
+        assert!(!config.hints.notes);
+
+        let nested = Config::parse(json!({
+            "minira": { "checkOnSave": false, "hints": { "notes": false } },
+        }))
+        .expect("valid settings");
+        assert_eq!(nested, config);
+    }
+
+    #[test]
+    fn cargo_args() {
+        let config = Config::parse(json!({
+            "features": ["gpu", "serde"],
+            "noDefaultFeatures": true,
+            "target": "x86_64-pc-windows-msvc",
+        }))
+        .expect("valid settings");
+        assert_eq!(
+            config.cargo.cargo_args(),
+            [
+                "--features",
+                "gpu,serde",
+                "--no-default-features",
+                "--target",
+                "x86_64-pc-windows-msvc",
+            ]
+        );
+        assert!(!config.check_changed(&config.clone()));
+        assert!(config.check_changed(&Config::default()));
+    }
+
+    #[test]
+    fn configurations() {
+        let config = Config::parse(json!({
+            "features": ["gpu"],
+            "configurations": [
+                { "target": "x86_64-pc-windows-msvc" },
+                { "features": ["gpu"] },
+                { "allFeatures": true },
+            ],
+        }))
+        .expect("valid settings");
+        assert_eq!(
+            config
+                .all_configurations()
+                .iter()
+                .map(CargoOptions::describe)
+                .collect::<Vec<_>>(),
+            [
+                "`--features gpu`",
+                "`--target x86_64-pc-windows-msvc`",
+                "`--all-features`",
+            ]
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! code for tracking the edits made to documents since the last workspace check
+//! the data of a check is computed from the files on disk, so positions in the edited document
+//! must be mapped back before querying it
+
+use tower_lsp::lsp_types::{Position, Range};
+
+#[derive(Debug, Default)]
+pub struct EditLog {
+    /// edits in the order they were applied
+    edits: Vec<Edit>,
+    /// set when the whole document was replaced, no position can be mapped afterwards
+    replaced: bool,
+}
+
+#[derive(Debug)]
+struct Edit {
+    /// the replaced range in the document before the edit
+    range: Range,
+    /// the end of the inserted text in the document after the edit
+    end: Position,
+}
+
+impl EditLog {
+    /// record an edit that replaced the given range with the given text
+    /// a range of `None` means the whole document was replaced
+    pub fn push(&mut self, range: Option<Range>, text: &str) {
+        let Some(range) = range else {
+            self.replaced = true;
+            return;
+        };
+
+        #[expect(clippy::cast_possible_truncation)]
+        let end = match text.rsplit_once('\n') {
+            Some((before, after)) => Position {
+                line: range.start.line + before.matches('\n').count() as u32 + 1,
+                character: after.chars().count() as u32,
+            },
+            None => Position {
+                line: range.start.line,
+                character: range.start.character + text.chars().count() as u32,
+            },
+        };
+        self.edits.push(Edit { range, end });
+    }
+
+    /// map a position in the current document to the position in the document at the time of the
+    /// last check, returns `None` if the position is inside text that was inserted since then
+    pub fn map_to_checked(&self, mut position: Position) -> Option<Position> {
+        if self.replaced {
+            return None;
+        }
+
+        // undo the edits from the most recent to the oldest
+        for Edit { range, end } in self.edits.iter().rev() {
+            // positions before the edit are not affected by it
+            if position <= range.start {
+                continue;
+            }
+            if position < *end {
+                return None;
+            }
+            position = if position.line == end.line {
+                Position {
+                    line: range.end.line,
+                    character: range.end.character + (position.character - end.character),
+                }
+            } else {
+                Position {
+                    line: position.line - end.line + range.end.line,
+                    character: position.character,
+                }
+            };
+        }
+
+        Some(position)
+    }
+
+    /// map a position in the document at the time of the last check to the position in the
+    /// current document, returns `None` if the position is inside text that was replaced since then
+    pub fn map_from_checked(&self, position: Position) -> Option<Position> {
+        if self.replaced {
+            return None;
+        }
+
+        // redo the edits from the oldest to the most recent
+        self.edits
+            .iter()
+            .try_fold(position, |position, edit| edit.redo(position))
+    }
+
+    /// map a range in the document at the time of the last check to the range in the current
+    /// document, returns `None` if either end is inside text that was replaced since then
+    pub fn map_range_from_checked(&self, range: Range) -> Option<Range> {
+        Some(Range {
+            start: self.map_from_checked(range.start)?,
+            end: self.map_from_checked(range.end)?,
+        })
+    }
+
+    /// like `map_range_from_checked`, but also returns `None` if text inside the range was edited
+    /// since then, for ranges that are replaced by an edit of the server
+    pub fn map_unedited_range_from_checked(&self, mut range: Range) -> Option<Range> {
+        if self.replaced {
+            return None;
+        }
+
+        for edit in &self.edits {
+            // text inserted at either end of the range is not part of it
+            if edit.range.start < range.end && edit.range.end > range.start {
+                return None;
+            }
+            // an edit that ends at the start of the range, such as an insertion, is before it
+            let start = if edit.range.end == range.start {
+                edit.end
+            } else {
+                edit.redo(range.start)?
+            };
+            let end = edit.redo(range.end)?.max(start);
+            range = Range { start, end };
+        }
+
+        Some(range)
+    }
+}
+
+impl Edit {
+    /// map a position in the document before the edit to the position after it
+    fn redo(&self, position: Position) -> Option<Position> {
+        let Self { range, end } = self;
+        if position <= range.start {
+            return Some(position);
+        }
+        if position < range.end {
+            return None;
+        }
+
+        Some(if position.line == range.end.line {
+            Position {
+                line: end.line,
+                character: end.character + (position.character - range.end.character),
+            }
+        } else {
+            Position {
+                line: position.line - range.end.line + end.line,
+                character: position.character,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(line: u32, character: u32) -> Position {
+        Position::new(line, character)
+    }
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Range {
+        Range::new(position(start.0, start.1), position(end.0, end.1))
+    }
+
+    fn edit_log(edits: &[(Range, &str)]) -> EditLog {
+        let mut log = EditLog::default();
+        for (range, text) in edits {
+            log.push(Some(*range), text);
+        }
+        log
+    }
+
+    #[test]
+    fn insert() {
+        let log = edit_log(&[(range((1, 4), (1, 4)), "abc")]);
+
+        // before, inside and after the inserted text
+        assert_eq!(log.map_to_checked(position(1, 2)), Some(position(1, 2)));
+        assert_eq!(log.map_to_checked(position(1, 4)), Some(position(1, 4)));
+        assert_eq!(log.map_to_checked(position(1, 5)), None);
+        assert_eq!(log.map_to_checked(position(1, 7)), Some(position(1, 4)));
+        assert_eq!(log.map_to_checked(position(1, 9)), Some(position(1, 6)));
+        assert_eq!(log.map_to_checked(position(2, 3)), Some(position(2, 3)));
+
+        assert_eq!(log.map_from_checked(position(1, 2)), Some(position(1, 2)));
+        assert_eq!(log.map_from_checked(position(1, 4)), Some(position(1, 4)));
+        assert_eq!(log.map_from_checked(position(1, 6)), Some(position(1, 9)));
+        assert_eq!(log.map_from_checked(position(2, 3)), Some(position(2, 3)));
+    }
+
+    #[test]
+    fn insert_lines() {
+        let log = edit_log(&[(range((1, 4), (1, 4)), "x\ny\nz")]);
+
+        assert_eq!(log.map_to_checked(position(0, 9)), Some(position(0, 9)));
+        assert_eq!(log.map_to_checked(position(2, 0)), None);
+        assert_eq!(log.map_to_checked(position(3, 1)), Some(position(1, 4)));
+        assert_eq!(log.map_to_checked(position(3, 5)), Some(position(1, 8)));
+        assert_eq!(log.map_to_checked(position(5, 0)), Some(position(3, 0)));
+
+        assert_eq!(log.map_from_checked(position(0, 9)), Some(position(0, 9)));
+        assert_eq!(log.map_from_checked(position(1, 8)), Some(position(3, 5)));
+        assert_eq!(log.map_from_checked(position(3, 0)), Some(position(5, 0)));
+    }
+
+    #[test]
+    fn delete() {
+        let log = edit_log(&[(range((1, 2), (3, 4)), "")]);
+
+        assert_eq!(log.map_to_checked(position(0, 5)), Some(position(0, 5)));
+        assert_eq!(log.map_to_checked(position(1, 2)), Some(position(1, 2)));
+        assert_eq!(log.map_to_checked(position(1, 5)), Some(position(3, 7)));
+        assert_eq!(log.map_to_checked(position(2, 0)), Some(position(4, 0)));
+
+        // positions inside the deleted text have no position in the current document
+        assert_eq!(log.map_from_checked(position(0, 5)), Some(position(0, 5)));
+        assert_eq!(log.map_from_checked(position(1, 2)), Some(position(1, 2)));
+        assert_eq!(log.map_from_checked(position(1, 3)), None);
+        assert_eq!(log.map_from_checked(position(2, 0)), None);
+        assert_eq!(log.map_from_checked(position(3, 4)), Some(position(1, 2)));
+        assert_eq!(log.map_from_checked(position(3, 9)), Some(position(1, 7)));
+        assert_eq!(log.map_from_checked(position(5, 1)), Some(position(3, 1)));
+    }
+
+    #[test]
+    fn replace() {
+        let log = edit_log(&[(range((1, 0), (1, 3)), "abcdef")]);
+
+        assert_eq!(log.map_to_checked(position(1, 2)), None);
+        assert_eq!(log.map_to_checked(position(1, 6)), Some(position(1, 3)));
+        assert_eq!(log.map_from_checked(position(1, 1)), None);
+        assert_eq!(log.map_from_checked(position(1, 5)), Some(position(1, 8)));
+    }
+
+    #[test]
+    fn several_edits() {
+        // a line inserted at the top, then a line deleted further down in the new document
+        let log = edit_log(&[(range((0, 0), (0, 0)), "//\n"), (range((5, 0), (6, 0)), "")]);
+
+        assert_eq!(log.map_from_checked(position(2, 3)), Some(position(3, 3)));
+        assert_eq!(log.map_from_checked(position(4, 1)), None);
+        assert_eq!(log.map_from_checked(position(5, 2)), Some(position(5, 2)));
+        assert_eq!(log.map_to_checked(position(0, 1)), None);
+        assert_eq!(log.map_to_checked(position(3, 3)), Some(position(2, 3)));
+        assert_eq!(log.map_to_checked(position(5, 2)), Some(position(5, 2)));
+
+        // mapping back and forth returns the same position outside of the edited text
+        for position in [position(1, 0), position(3, 7), position(9, 2)] {
+            let current = log.map_from_checked(position).expect("not edited");
+            assert_eq!(log.map_to_checked(current), Some(position));
+        }
+    }
+
+    #[test]
+    fn replaced_document() {
+        let mut log = edit_log(&[(range((1, 0), (1, 0)), "x")]);
+        log.push(None, "fn main() {}\n");
+
+        assert_eq!(log.map_to_checked(position(0, 0)), None);
+        assert_eq!(log.map_from_checked(position(0, 0)), None);
+        assert_eq!(
+            log.map_unedited_range_from_checked(range((0, 0), (0, 1))),
+            None
+        );
+    }
+
+    #[test]
+    fn ranges() {
+        let checked = range((2, 4), (2, 10));
+
+        // text inserted inside the range moves its end, unless the range must be unedited
+        let log = edit_log(&[(range((2, 6), (2, 6)), "ab")]);
+        assert_eq!(
+            log.map_range_from_checked(checked),
+            Some(range((2, 4), (2, 12)))
+        );
+        assert_eq!(log.map_unedited_range_from_checked(checked), None);
+
+        // text inserted before the range moves it
+        for edit in [range((1, 0), (1, 0)), range((2, 0), (2, 0))] {
+            let log = edit_log(&[(edit, "\n")]);
+            assert_eq!(
+                log.map_range_from_checked(checked),
+                Some(range((3, 4), (3, 10)))
+            );
+            assert_eq!(
+                log.map_unedited_range_from_checked(checked),
+                Some(range((3, 4), (3, 10)))
+            );
+        }
+
+        // text inserted at either end is not part of an unedited range
+        let log = edit_log(&[(range((2, 4), (2, 4)), "\n")]);
+        assert_eq!(
+            log.map_range_from_checked(checked),
+            Some(range((2, 4), (3, 6)))
+        );
+        assert_eq!(
+            log.map_unedited_range_from_checked(checked),
+            Some(range((3, 0), (3, 6)))
+        );
+        let log = edit_log(&[(range((2, 10), (2, 10)), "\n")]);
+        assert_eq!(log.map_range_from_checked(checked), Some(checked));
+        assert_eq!(log.map_unedited_range_from_checked(checked), Some(checked));
+        let log = edit_log(&[(range((2, 4), (2, 4)), "ab")]);
+        assert_eq!(
+            log.map_unedited_range_from_checked(range((2, 4), (2, 4))),
+            Some(range((2, 6), (2, 6)))
+        );
+
+        // a deletion that overlaps the range removes it
+        let log = edit_log(&[(range((2, 0), (2, 5)), "")]);
+        assert_eq!(log.map_range_from_checked(checked), None);
+        assert_eq!(log.map_unedited_range_from_checked(checked), None);
+    }
+}
@@ -0,0 +1,321 @@
+//! conversion of positions between the encoding negotiated with the client and the char columns
+//! used by the server
+//! the rope of a document, the embedded compiler and the parser all count columns in chars, so
+//! positions are converted when they are received from or sent to the client
+
+use std::collections::HashMap;
+use std::fs;
+
+use dashmap::DashMap;
+use ropey::{Rope, RopeSlice};
+use tower_lsp::lsp_types::{
+    ClientCapabilities, Location, Position, PositionEncodingKind, Range, Url,
+};
+
+use crate::Backend;
+
+/// the unit that the columns of positions exchanged with the client count
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PositionEncoding {
+    /// bytes
+    Utf8,
+    /// UTF-16 code units, which every client must support
+    #[default]
+    Utf16,
+    /// chars, which needs no conversion
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// choose the encoding from the ones supported by the client, preferring UTF-8 since it is the
+    /// encoding of the source files, then UTF-32 since it needs no conversion
+    pub fn negotiate(capabilities: &ClientCapabilities) -> Self {
+        let supported = capabilities
+            .general
+            .as_ref()
+            .and_then(|general| general.position_encodings.as_deref())
+            .unwrap_or_default();
+
+        [Self::Utf8, Self::Utf32]
+            .into_iter()
+            .find(|encoding| supported.contains(&encoding.kind()))
+            .unwrap_or_default()
+    }
+
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            Self::Utf8 => PositionEncodingKind::UTF8,
+            Self::Utf16 => PositionEncodingKind::UTF16,
+            Self::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+
+    /// the number of code units of a char
+    fn char_units(self, c: char) -> u32 {
+        #[expect(clippy::cast_possible_truncation)]
+        match self {
+            Self::Utf8 => c.len_utf8() as u32,
+            Self::Utf16 => c.len_utf16() as u32,
+            Self::Utf32 => 1,
+        }
+    }
+
+    /// the number of code units of the text
+    pub fn units(self, text: &str) -> u32 {
+        text.chars().map(|c| self.char_units(c)).sum()
+    }
+
+    /// convert a column counting chars into a column counting code units
+    /// columns past the end of the line are clamped to the end of the line
+    pub fn encode_column(self, line: RopeSlice<'_>, column: u32) -> u32 {
+        line_chars(line)
+            .take(column as usize)
+            .map(|c| self.char_units(c))
+            .sum()
+    }
+
+    /// convert a column counting code units into a column counting chars
+    /// a column in the middle of a char is rounded down to the start of the char, and columns past
+    /// the end of the line are clamped to the end of the line
+    pub fn decode_column(self, line: RopeSlice<'_>, column: u32) -> u32 {
+        let mut units = 0;
+        let mut chars = 0;
+        for c in line_chars(line) {
+            units += self.char_units(c);
+            if units > column {
+                break;
+            }
+            chars += 1;
+        }
+        chars
+    }
+
+    /// convert a position in the document received from the client into a position counting chars
+    pub fn decode_position(self, document: &Rope, position: Position) -> Position {
+        if self == Self::Utf32 || position.line as usize >= document.len_lines() {
+            return position;
+        }
+
+        let line = document.line(position.line as usize);
+        Position {
+            line: position.line,
+            character: self.decode_column(line, position.character),
+        }
+    }
+
+    /// convert a position in the document counting chars into a position sent to the client
+    pub fn encode_position(self, document: &Rope, position: Position) -> Position {
+        if self == Self::Utf32 || position.line as usize >= document.len_lines() {
+            return position;
+        }
+
+        let line = document.line(position.line as usize);
+        Position {
+            line: position.line,
+            character: self.encode_column(line, position.character),
+        }
+    }
+}
+
+/// the chars of a line without the line ending
+fn line_chars(line: RopeSlice<'_>) -> impl Iterator<Item = char> + '_ {
+    line.chars().take_while(|c| !matches!(c, '\n' | '\r'))
+}
+
+/// converts the positions in any file of the workspace, using the opened document if there is one
+/// and the file on disk otherwise
+pub struct Converter<'a> {
+    encoding: PositionEncoding,
+    opened_files: &'a DashMap<Url, (i32, Rope)>,
+    /// files that are not opened, read when a position in them is first converted
+    files: HashMap<Url, Option<Rope>>,
+}
+
+impl<'a> Converter<'a> {
+    pub fn new(backend: &'a Backend) -> Self {
+        Self {
+            encoding: backend.encoding(),
+            opened_files: &backend.opened_files,
+            files: HashMap::new(),
+        }
+    }
+
+    /// apply the function to the text of the file, positions are left as is if it cannot be read
+    fn convert(
+        &mut self,
+        uri: &Url,
+        position: Position,
+        f: impl FnOnce(PositionEncoding, &Rope, Position) -> Position,
+    ) -> Position {
+        if self.encoding == PositionEncoding::Utf32 {
+            return position;
+        }
+        if let Some(document) = self.opened_files.get(uri) {
+            return f(self.encoding, &document.1, position);
+        }
+
+        let file = self.files.entry(uri.clone()).or_insert_with(|| {
+            let text = fs::read_to_string(uri.to_file_path().ok()?).ok()?;
+            Some(Rope::from(text))
+        });
+        match file {
+            Some(document) => f(self.encoding, document, position),
+            None => position,
+        }
+    }
+
+    /// convert a position received from the client into a position counting chars
+    pub fn decode(&mut self, uri: &Url, position: Position) -> Position {
+        self.convert(uri, position, PositionEncoding::decode_position)
+    }
+
+    pub fn decode_range(&mut self, uri: &Url, range: Range) -> Range {
+        Range {
+            start: self.decode(uri, range.start),
+            end: self.decode(uri, range.end),
+        }
+    }
+
+    /// convert a position counting chars into a position sent to the client
+    pub fn encode(&mut self, uri: &Url, position: Position) -> Position {
+        self.convert(uri, position, PositionEncoding::encode_position)
+    }
+
+    pub fn encode_range(&mut self, uri: &Url, range: Range) -> Range {
+        Range {
+            start: self.encode(uri, range.start),
+            end: self.encode(uri, range.end),
+        }
+    }
+
+    pub fn encode_location(&mut self, Location { uri, range }: Location) -> Location {
+        let range = self.encode_range(&uri, range);
+        Location { uri, range }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::GeneralClientCapabilities;
+
+    use super::*;
+
+    /// `é` is 2 bytes and 1 UTF-16 code unit, `😀` is 4 bytes and 2 UTF-16 code units, and `中` is
+    /// 3 bytes and 1 UTF-16 code unit
+    const DOCUMENT: &str = "let s = \"é😀\";\r\nlet 中 = s;\nfn main() {}";
+
+    fn capabilities(encodings: Option<Vec<PositionEncodingKind>>) -> ClientCapabilities {
+        ClientCapabilities {
+            general: Some(GeneralClientCapabilities {
+                position_encodings: encodings,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn negotiate() {
+        let negotiate = |encodings| PositionEncoding::negotiate(&capabilities(encodings));
+
+        assert_eq!(negotiate(None), PositionEncoding::Utf16);
+        assert_eq!(
+            negotiate(Some(Vec::from([PositionEncodingKind::UTF16]))),
+            PositionEncoding::Utf16
+        );
+        assert_eq!(
+            negotiate(Some(Vec::from([
+                PositionEncodingKind::UTF16,
+                PositionEncodingKind::UTF32,
+            ]))),
+            PositionEncoding::Utf32
+        );
+        assert_eq!(
+            negotiate(Some(Vec::from([
+                PositionEncodingKind::UTF32,
+                PositionEncodingKind::UTF8,
+            ]))),
+            PositionEncoding::Utf8
+        );
+    }
+
+    #[test]
+    fn encode_non_ascii() {
+        let document = Rope::from(DOCUMENT);
+        // the closing quote after the emoji
+        let quote = Position::new(0, 11);
+
+        let encode = |encoding: PositionEncoding| encoding.encode_position(&document, quote);
+        assert_eq!(encode(PositionEncoding::Utf8), Position::new(0, 15));
+        assert_eq!(encode(PositionEncoding::Utf16), Position::new(0, 12));
+        assert_eq!(encode(PositionEncoding::Utf32), quote);
+
+        // the `=` after the CJK character
+        let equals = Position::new(1, 6);
+        assert_eq!(
+            PositionEncoding::Utf8.encode_position(&document, equals),
+            Position::new(1, 8)
+        );
+        assert_eq!(
+            PositionEncoding::Utf16.encode_position(&document, equals),
+            equals
+        );
+    }
+
+    #[test]
+    fn decode_non_ascii() {
+        let document = Rope::from(DOCUMENT);
+
+        let decode = |encoding: PositionEncoding, character| {
+            encoding
+                .decode_position(&document, Position::new(0, character))
+                .character
+        };
+        assert_eq!(decode(PositionEncoding::Utf8, 15), 11);
+        assert_eq!(decode(PositionEncoding::Utf16, 12), 11);
+        assert_eq!(decode(PositionEncoding::Utf32, 11), 11);
+
+        // a column in the middle of the emoji is rounded down to its start
+        assert_eq!(decode(PositionEncoding::Utf8, 12), 10);
+        assert_eq!(decode(PositionEncoding::Utf16, 11), 10);
+    }
+
+    #[test]
+    fn round_trip() {
+        let document = Rope::from(DOCUMENT);
+
+        for encoding in [
+            PositionEncoding::Utf8,
+            PositionEncoding::Utf16,
+            PositionEncoding::Utf32,
+        ] {
+            for (line, text) in DOCUMENT.lines().enumerate() {
+                for character in 0..=text.chars().count() {
+                    #[expect(clippy::cast_possible_truncation)]
+                    let position = Position::new(line as u32, character as u32);
+                    let encoded = encoding.encode_position(&document, position);
+                    assert_eq!(encoding.decode_position(&document, encoded), position);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn past_end_of_line() {
+        let document = Rope::from(DOCUMENT);
+
+        // the line ending is not part of the line
+        assert_eq!(
+            PositionEncoding::Utf16
+                .decode_position(&document, Position::new(0, 100))
+                .character,
+            13
+        );
+        assert_eq!(
+            PositionEncoding::Utf8
+                .encode_position(&document, Position::new(2, 100))
+                .character,
+            12
+        );
+    }
+}
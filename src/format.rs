@@ -0,0 +1,538 @@
+//! code for formatting documents with rustfmt
+//! - the configuration is discovered to match what `cargo fmt` would use for the package that owns
+//!   the document
+//! - the formatted text is diffed against the document to produce minimal edits, so the cursor and
+//!   undo history of the editor are disturbed as little as possible
+
+use std::fs;
+use std::ops::Range as ByteRange;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use similar::{DiffOp, TextDiff};
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range, TextEdit};
+
+use crate::encoding::PositionEncoding;
+
+/// edition used for documents that do not belong to a package, such as unsaved files
+const DEFAULT_EDITION: &str = "2021";
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct FormatConfig {
+    /// edition of the package that owns the document
+    pub edition: String,
+    /// the closest `rustfmt.toml` or `.rustfmt.toml` above the document
+    pub config_path: Option<PathBuf>,
+    /// the toolchain pinned by the closest `rust-toolchain.toml` or `rust-toolchain` above the
+    /// document, `None` uses the default toolchain
+    pub toolchain: Option<String>,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            edition: DEFAULT_EDITION.to_owned(),
+            config_path: None,
+            toolchain: None,
+        }
+    }
+}
+
+impl FormatConfig {
+    /// discover the configuration by walking up from the document path
+    pub fn discover(path: &Path) -> Self {
+        let Some(dir) = path.parent() else {
+            return Self::default();
+        };
+
+        Self {
+            edition: edition(dir).unwrap_or_else(|| DEFAULT_EDITION.to_owned()),
+            config_path: find_file(dir, &["rustfmt.toml", ".rustfmt.toml"]),
+            toolchain: toolchain(dir),
+        }
+    }
+
+    /// arguments passed to rustfmt, the toolchain must come first since it is read by rustup
+    /// the toolchain is only passed to the rustup proxy, a rustfmt binary rejects it as a file
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(toolchain) = self.toolchain.as_ref().filter(|_| rustup_proxy()) {
+            args.push(format!("+{toolchain}"));
+        }
+        args.extend(["--edition".to_owned(), self.edition.clone()]);
+        if let Some(config_path) = &self.config_path {
+            args.extend([
+                "--config-path".to_owned(),
+                config_path.to_string_lossy().into_owned(),
+            ]);
+        }
+        args
+    }
+}
+
+/// whether the `rustfmt` found in `PATH` is the proxy of rustup rather than a rustfmt binary
+/// rustup installs its proxies next to itself as symlinks or hard links to its own binary
+fn rustup_proxy() -> bool {
+    static PROXY: OnceLock<bool> = OnceLock::new();
+    *PROXY.get_or_init(|| {
+        let Some(paths) = std::env::var_os("PATH") else {
+            return false;
+        };
+        let Some(rustfmt) = std::env::split_paths(&paths)
+            .map(|dir| dir.join(format!("rustfmt{}", std::env::consts::EXE_SUFFIX)))
+            .find(|path| path.is_file())
+        else {
+            return false;
+        };
+        let Ok(target) = fs::canonicalize(&rustfmt) else {
+            return false;
+        };
+        if target.file_stem().is_some_and(|stem| stem == "rustup") {
+            return true;
+        }
+
+        let rustup = rustfmt.with_file_name(format!("rustup{}", std::env::consts::EXE_SUFFIX));
+        match (fs::metadata(&target), fs::metadata(rustup)) {
+            (Ok(rustfmt), Ok(rustup)) => rustfmt.len() == rustup.len(),
+            _ => false,
+        }
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    package: Option<Package>,
+    workspace: Option<WorkspaceManifest>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Package {
+    edition: Option<Inheritable>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkspaceManifest {
+    package: Option<WorkspacePackage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkspacePackage {
+    edition: Option<String>,
+}
+
+/// a package field that is either set directly or inherited with `field.workspace = true`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Inheritable {
+    Value(String),
+    Workspace { workspace: bool },
+}
+
+/// the edition of the closest package above the directory, following `edition.workspace = true`
+/// to the workspace manifest
+fn edition(dir: &Path) -> Option<String> {
+    let mut manifests = dir
+        .ancestors()
+        .map(|dir| dir.join("Cargo.toml"))
+        .filter_map(|path| toml::from_str::<Manifest>(&fs::read_to_string(path).ok()?).ok());
+
+    let mut manifest = manifests.find(|manifest| manifest.package.is_some())?;
+    let package = manifest.package.take()?;
+    match package.edition {
+        Some(Inheritable::Value(edition)) => Some(edition),
+        // the root package of a workspace declares the workspace in the same manifest
+        Some(Inheritable::Workspace { workspace: true }) => std::iter::once(manifest)
+            .chain(manifests)
+            .find_map(|manifest| manifest.workspace)
+            .and_then(|workspace| workspace.package?.edition),
+        // cargo defaults to the first edition when none is specified
+        Some(Inheritable::Workspace { workspace: false }) | None => Some("2015".to_owned()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolchainFile {
+    toolchain: Toolchain,
+}
+
+#[derive(Debug, Deserialize)]
+struct Toolchain {
+    channel: Option<String>,
+}
+
+/// the channel of the closest toolchain file above the directory
+/// the legacy `rust-toolchain` file may also contain just the name of the channel, and takes
+/// precedence over `rust-toolchain.toml` like it does in rustup
+fn toolchain(dir: &Path) -> Option<String> {
+    let path = find_file(dir, &["rust-toolchain", "rust-toolchain.toml"])?;
+    let contents = fs::read_to_string(&path).ok()?;
+
+    match toml::from_str::<ToolchainFile>(&contents) {
+        Ok(file) => file.toolchain.channel,
+        Err(_) if path.extension().is_none() => {
+            let channel = contents.trim();
+            (!channel.is_empty() && !channel.contains(char::is_whitespace))
+                .then(|| channel.to_owned())
+        }
+        Err(_) => None,
+    }
+}
+
+/// the first of the file names that exists in the closest directory above the given one
+fn find_file(dir: &Path, names: &[&str]) -> Option<PathBuf> {
+    dir.ancestors()
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// compute the edits that turn the original text into the new text
+/// changed lines are diffed again by characters, so changing a single space results in an edit
+/// that only replaces that space
+pub fn diff_edits(original: &str, new_text: &str, encoding: PositionEncoding) -> Vec<TextEdit> {
+    let diff = TextDiff::from_lines(original, new_text);
+    let old_offsets = offsets(diff.old_slices());
+    let new_offsets = offsets(diff.new_slices());
+    let lines = LineIndex::new(original, encoding);
+
+    let mut edits = Vec::new();
+    for op in diff.ops() {
+        let (old, new) = match *op {
+            DiffOp::Equal { .. } => continue,
+            DiffOp::Delete {
+                old_index,
+                old_len,
+                new_index,
+            } => (old_index..old_index + old_len, new_index..new_index),
+            DiffOp::Insert {
+                old_index,
+                new_index,
+                new_len,
+            } => (old_index..old_index, new_index..new_index + new_len),
+            DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            } => (
+                old_index..old_index + old_len,
+                new_index..new_index + new_len,
+            ),
+        };
+        let old = old_offsets[old.start]..old_offsets[old.end];
+        let new = new_offsets[new.start]..new_offsets[new.end];
+
+        let hunk = (!old.is_empty() && !new.is_empty())
+            .then(|| char_edits(original, new_text, &old, &new))
+            .flatten()
+            .unwrap_or_else(|| Vec::from([(old, new)]));
+        edits.extend(hunk.into_iter().map(|(old, new)| TextEdit {
+            range: Range {
+                start: lines.position(old.start),
+                end: lines.position(old.end),
+            },
+            new_text: new_text[new].to_owned(),
+        }));
+    }
+
+    edits
+}
+
+/// diff the replaced lines by characters, returning the byte ranges of each change in the original
+/// and new text
+/// returns `None` if a change would split a `\r\n` line ending, since positions in the middle of
+/// one are not valid
+fn char_edits(
+    original: &str,
+    new_text: &str,
+    old: &ByteRange<usize>,
+    new: &ByteRange<usize>,
+) -> Option<Vec<(ByteRange<usize>, ByteRange<usize>)>> {
+    let old_text = &original[old.clone()];
+    let new_lines = &new_text[new.clone()];
+    let diff = TextDiff::from_chars(old_text, new_lines);
+    let old_offsets = offsets(diff.old_slices());
+    let new_offsets = offsets(diff.new_slices());
+
+    // adjacent changes are grouped into a single edit
+    let mut edits = Vec::<(ByteRange<usize>, ByteRange<usize>)>::new();
+    for op in diff.ops() {
+        if let DiffOp::Equal { .. } = op {
+            continue;
+        }
+        let old_range = op.old_range();
+        let new_range = op.new_range();
+        let old_range =
+            old.start + old_offsets[old_range.start]..old.start + old_offsets[old_range.end];
+        let new_range =
+            new.start + new_offsets[new_range.start]..new.start + new_offsets[new_range.end];
+
+        match edits.last_mut() {
+            Some((old, new)) if old.end == old_range.start && new.end == new_range.start => {
+                old.end = old_range.end;
+                new.end = new_range.end;
+            }
+            _ => edits.push((old_range, new_range)),
+        }
+    }
+
+    let splits_line_ending =
+        |offset: usize| original[..offset].ends_with('\r') && original[offset..].starts_with('\n');
+    edits
+        .iter()
+        .all(|(old, _)| !splits_line_ending(old.start) && !splits_line_ending(old.end))
+        .then_some(edits)
+}
+
+/// the byte offset of the start of each slice, followed by the end of the last slice
+fn offsets(slices: &[&str]) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(slices.iter().scan(0, |offset, slice| {
+            *offset += slice.len();
+            Some(*offset)
+        }))
+        .collect()
+}
+
+/// conversion of byte offsets into positions with columns in the negotiated encoding
+struct LineIndex<'a> {
+    text: &'a str,
+    encoding: PositionEncoding,
+    /// byte offset of the start of each line
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(text: &'a str, encoding: PositionEncoding) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+        Self {
+            text,
+            encoding,
+            starts,
+        }
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let line = self.starts.partition_point(|start| *start <= offset) - 1;
+        let character = self.encoding.units(&self.text[self.starts[line]..offset]);
+
+        #[expect(clippy::cast_possible_truncation)]
+        Position {
+            line: line as u32,
+            character,
+        }
+    }
+}
+
+/// parse the errors that rustfmt printed to stderr into diagnostics, such as
+/// ```text
+/// error: this file contains an unclosed delimiter
+///  --> <stdin>:4:10
+/// ```
+/// the columns of rustfmt count chars like the positions of the server
+pub fn parse_errors(stderr: &str) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
+    let mut message = None;
+
+    for line in stderr.lines() {
+        if let Some((level, rest)) = line.split_once(": ") {
+            let severity = match level.split('[').next() {
+                Some("error") => DiagnosticSeverity::ERROR,
+                Some("warning") => DiagnosticSeverity::WARNING,
+                _ => continue,
+            };
+            message = Some((severity, rest.to_owned()));
+            continue;
+        }
+
+        // the location follows the message, and the message is only used once
+        let Some(location) = line.trim_start().strip_prefix("--> ") else {
+            continue;
+        };
+        let Some((severity, message)) = message.take() else {
+            continue;
+        };
+        let mut parts = location.rsplitn(3, ':');
+        let (Some(Ok(column)), Some(Ok(line))) = (
+            parts.next().map(str::parse::<usize>),
+            parts.next().map(str::parse::<usize>),
+        ) else {
+            continue;
+        };
+
+        // the range covers the character at the location
+        #[expect(clippy::cast_possible_truncation)]
+        let range = Range {
+            start: Position::new(
+                line.saturating_sub(1) as u32,
+                column.saturating_sub(1) as u32,
+            ),
+            end: Position::new(line.saturating_sub(1) as u32, column as u32),
+        };
+        errors.push(Diagnostic {
+            range,
+            severity: Some(severity),
+            source: Some("rustfmt".to_owned()),
+            message,
+            ..Default::default()
+        });
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// apply the edits to the text, which must not overlap and be in order
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let offset = |position: Position| {
+            let start = text
+                .split_inclusive('\n')
+                .take(position.line as usize)
+                .map(str::len)
+                .sum::<usize>();
+            let line = &text[start..];
+            let mut units = 0;
+            let column = line
+                .char_indices()
+                .find(|(_, c)| {
+                    let found = units >= position.character as usize;
+                    units += c.len_utf16();
+                    found
+                })
+                .map_or(line.len(), |(idx, _)| idx);
+            start + column
+        };
+
+        let mut result = text.to_owned();
+        for edit in edits.iter().rev() {
+            result.replace_range(
+                offset(edit.range.start)..offset(edit.range.end),
+                &edit.new_text,
+            );
+        }
+        result
+    }
+
+    fn edit(start: (u32, u32), end: (u32, u32), new_text: &str) -> TextEdit {
+        TextEdit {
+            range: Range {
+                start: Position::new(start.0, start.1),
+                end: Position::new(end.0, end.1),
+            },
+            new_text: new_text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn single_space() {
+        let original = "fn main() {\n    let x  = 1;\n}\n";
+        let formatted = "fn main() {\n    let x = 1;\n}\n";
+        let edits = diff_edits(original, formatted, PositionEncoding::Utf16);
+
+        assert_eq!(edits, Vec::from([edit((1, 10), (1, 11), "")]));
+        assert_eq!(apply(original, &edits), formatted);
+    }
+
+    #[test]
+    fn utf16_columns() {
+        let original = "let s = \"é😀\";let x=1;\n";
+        let formatted = "let s = \"é😀\";\nlet x = 1;\n";
+        let edits = diff_edits(original, formatted, PositionEncoding::Utf16);
+
+        // `é` is one UTF-16 code unit and `😀` is two
+        assert_eq!(edits[0].range.start, Position::new(0, 14));
+        assert_eq!(apply(original, &edits), formatted);
+    }
+
+    #[test]
+    fn crlf() {
+        let original = "fn main() {\r\nlet x=1;\r\n}\r\n";
+        let formatted = "fn main() {\r\n    let x = 1;\r\n}\r\n";
+        let edits = diff_edits(original, formatted, PositionEncoding::Utf16);
+
+        assert!(edits.iter().all(|edit| edit.range.start.line == 1));
+        assert_eq!(apply(original, &edits), formatted);
+    }
+
+    #[test]
+    fn crlf_to_lf() {
+        let original = "fn a() {}\r\nfn b() {}\r\n";
+        let formatted = "fn a() {}\nfn b() {}\n";
+        let edits = diff_edits(original, formatted, PositionEncoding::Utf16);
+
+        // removing only the `\r` would leave a position in the middle of a line ending
+        assert!(edits.iter().all(|edit| edit.range.start.character == 0));
+        assert_eq!(apply(original, &edits), formatted);
+    }
+
+    #[test]
+    fn missing_trailing_newline() {
+        let original = "fn main() {}";
+        let formatted = "fn main() {}\n";
+        let edits = diff_edits(original, formatted, PositionEncoding::Utf16);
+
+        assert_eq!(edits, Vec::from([edit((0, 12), (0, 12), "\n")]));
+        assert_eq!(apply(original, &edits), formatted);
+    }
+
+    #[test]
+    fn missing_trailing_newline_with_changes() {
+        let original = "fn a(){}\nfn b(){}";
+        let formatted = "fn a() {}\nfn b() {}\n";
+        let edits = diff_edits(original, formatted, PositionEncoding::Utf16);
+
+        assert_eq!(apply(original, &edits), formatted);
+    }
+
+    #[test]
+    fn inserted_and_deleted_lines() {
+        let original = "use a;\n\n\nfn b() {}\n";
+        let formatted = "use a;\n\nfn b() {}\n\nfn c() {}\n";
+        let edits = diff_edits(original, formatted, PositionEncoding::Utf16);
+
+        assert_eq!(apply(original, &edits), formatted);
+    }
+
+    #[test]
+    fn workspace_edition() {
+        let root = std::env::temp_dir().join(format!("minira-edition-{}", std::process::id()));
+        let member = root.join("member");
+        fs::create_dir_all(&member).expect("temporary directory is writable");
+        fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"root\"\nedition.workspace = true\n\n\
+             [workspace]\nmembers = [\"member\"]\n\n[workspace.package]\nedition = \"2018\"\n",
+        )
+        .expect("temporary directory is writable");
+        fs::write(
+            member.join("Cargo.toml"),
+            "[package]\nname = \"member\"\nedition = { workspace = true }\n",
+        )
+        .expect("temporary directory is writable");
+
+        let root_edition = edition(&root);
+        let member_edition = edition(&member);
+        fs::remove_dir_all(&root).expect("temporary directory is writable");
+        assert_eq!(root_edition.as_deref(), Some("2018"));
+        assert_eq!(member_edition.as_deref(), Some("2018"));
+    }
+
+    #[test]
+    fn rustfmt_errors() {
+        let stderr = "error: this file contains an unclosed delimiter\n --> <stdin>:2:9\n  |\n2 | fn é( {}\n  |     -   ^\n\nWarning: can't set `x = y`\n";
+        let errors = parse_errors(stderr);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "this file contains an unclosed delimiter"
+        );
+        assert_eq!(errors[0].range.start, Position::new(1, 8));
+        assert_eq!(errors[0].source.as_deref(), Some("rustfmt"));
+    }
+}
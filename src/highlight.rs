@@ -0,0 +1,109 @@
+//! code related to the highlight table used for document highlights
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::*;
+
+#[derive(Debug, Default)]
+pub struct HighlightTable {
+    /// definitions and uses of local bindings in each file
+    /// invariant:
+    /// - the occurrences in the same file are sorted by their start position
+    pub occurrences: HashMap<Url, Vec<Occurrence>>,
+    /// exit points of the functions in each file
+    pub exit_points: HashMap<Url, Vec<ExitPoints>>,
+}
+
+impl HighlightTable {
+    pub fn merge_replace(&mut self, other: Self) {
+        for (url, occurrences) in other.occurrences {
+            self.occurrences.entry(url).insert_entry(occurrences);
+        }
+        for (url, exit_points) in other.exit_points {
+            self.exit_points.entry(url).insert_entry(exit_points);
+        }
+    }
+
+    /// drop the results of the files that `keep` rejects, such as files that were deleted
+    pub fn retain_files(&mut self, keep: impl Fn(&Url) -> bool) {
+        self.occurrences.retain(|url, _| keep(url));
+        self.exit_points.retain(|url, _| keep(url));
+    }
+
+    /// find the occurrences related to the one at the given position
+    /// exit points are highlighted on the `fn` keyword, a `return` or a `?`, and every occurrence
+    /// of a binding is highlighted on any of its occurrences
+    pub fn highlights(&self, url: &Url, position: Position) -> Option<Vec<DocumentHighlight>> {
+        let exit_points = self.exit_points.get(url).and_then(|exit_points| {
+            exit_points.iter().find(|exit_points| {
+                contains(exit_points.keyword, position)
+                    || exit_points
+                        .exits
+                        .iter()
+                        .filter(|exit| !exit.tail)
+                        .any(|exit| contains(exit.range, position))
+            })
+        });
+        if let Some(ExitPoints { keyword, exits }) = exit_points {
+            return Some(
+                std::iter::once(*keyword)
+                    .chain(exits.iter().map(|exit| exit.range))
+                    .map(|range| DocumentHighlight {
+                        range,
+                        kind: Some(DocumentHighlightKind::TEXT),
+                    })
+                    .collect(),
+            );
+        }
+
+        let occurrences = self.occurrences.get(url)?;
+        let idx = occurrences.partition_point(|occurrence| occurrence.range.end < position);
+        let binding = occurrences[idx..]
+            .iter()
+            .take_while(|occurrence| occurrence.range.start <= position)
+            .find(|occurrence| contains(occurrence.range, position))?
+            .binding;
+
+        Some(
+            occurrences
+                .iter()
+                .filter(|occurrence| occurrence.binding == binding)
+                .map(|occurrence| DocumentHighlight {
+                    range: occurrence.range,
+                    kind: Some(occurrence.kind),
+                })
+                .collect(),
+        )
+    }
+}
+
+/// a definition or use of a local binding
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Occurrence {
+    pub range: Range,
+    /// range of the identifier of the binding, which identifies the binding within the file
+    pub binding: Range,
+    /// write for definitions, assignments and mutable borrows, read otherwise
+    pub kind: DocumentHighlightKind,
+}
+
+/// the points at which a function can return
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct ExitPoints {
+    /// range of the `fn` keyword
+    pub keyword: Range,
+    pub exits: Vec<Exit>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Exit {
+    /// range of a `return` keyword, a `?` or the tail expression
+    pub range: Range,
+    /// whether the exit is the tail expression, which does not trigger the highlight itself
+    pub tail: bool,
+}
+
+fn contains(range: Range, position: Position) -> bool {
+    range.start <= position && position <= range.end
+}
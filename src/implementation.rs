@@ -0,0 +1,72 @@
+//! code related to the implementation table used for go-to-implementation and type hierarchies
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::*;
+
+#[derive(Debug, Default)]
+pub struct ImplementationTable {
+    /// identifiers naming a trait, trait method or type in each file
+    /// invariant:
+    /// - the references in the same file are sorted by their start position
+    /// - any two ranges cannot overlap
+    pub references: HashMap<Url, Vec<Reference>>,
+    /// locations of the implementations of traits, trait methods and types, keyed by their full
+    /// path
+    pub implementations: HashMap<String, Vec<Location>>,
+    /// supertypes and subtypes of traits and types, keyed by their full path
+    pub relations: HashMap<String, TypeRelations>,
+}
+
+impl ImplementationTable {
+    pub fn merge_replace(&mut self, other: Self) {
+        self.implementations.extend(other.implementations);
+        self.relations.extend(other.relations);
+        for (url, references) in other.references {
+            self.references.entry(url).insert_entry(references);
+        }
+    }
+
+    /// drop the results of the files that `keep` rejects, such as files that were deleted
+    pub fn retain_files(&mut self, keep: impl Fn(&Url) -> bool) {
+        self.references.retain(|url, _| keep(url));
+        for locations in self.implementations.values_mut() {
+            locations.retain(|location| keep(&location.uri));
+        }
+    }
+
+    /// find the reference whose identifier contains the given position
+    pub fn reference(&self, url: &Url, position: Position) -> Option<&Reference> {
+        let references = self.references.get(url)?;
+        let idx = references.partition_point(|reference| reference.range.end < position);
+
+        references
+            .get(idx)
+            .filter(|reference| reference.range.start <= position)
+    }
+
+    /// find the implementations of the trait, trait method or type named at the given position
+    pub fn implementations(&self, url: &Url, position: Position) -> Option<&[Location]> {
+        let reference = self.reference(url, position)?;
+        self.implementations.get(&reference.path).map(Vec::as_slice)
+    }
+}
+
+/// the neighbors of a trait or type in the type hierarchy
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct TypeRelations {
+    /// full paths of the supertraits of a trait, or the traits implemented by a type
+    pub supertypes: Vec<String>,
+    /// full paths of the traits extending a trait and the types implementing it
+    pub subtypes: Vec<String>,
+}
+
+/// an identifier that names a definition
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Reference {
+    /// range of the identifier
+    pub range: Range,
+    /// full path of the named definition
+    pub path: String,
+}
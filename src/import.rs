@@ -0,0 +1,311 @@
+//! code for inserting `use` statements into a document
+//! new imports are merged into an existing `use` with the same prefix when possible, otherwise
+//! they are added to the group of imports of the same kind (std, external crates, local crate)
+
+use itertools::Itertools;
+use tower_lsp::lsp_types::{Position, Range, TextEdit};
+
+/// a top level `use` statement
+struct UseStatement {
+    /// first line of the statement
+    start: usize,
+    /// last line of the statement
+    end: usize,
+    /// visibility and `use` keyword, such as `pub use `
+    keyword: String,
+    /// the use tree with normalized whitespace, such as `std::collections::{HashMap, HashSet}`
+    tree: String,
+}
+
+/// compute the edit that imports the given path into the document
+/// returns `None` if the path is already imported
+pub fn insert_use(text: &str, path: &str) -> Option<TextEdit> {
+    let (prefix, name) = path.rsplit_once("::")?;
+    let lines = text.lines().collect::<Vec<_>>();
+    let statements = use_statements(&lines);
+
+    // merge into an existing statement with the same prefix
+    for statement in &statements {
+        let Some(names) = merged_names(&statement.tree, prefix) else {
+            continue;
+        };
+        if names.contains(&name) {
+            return None;
+        }
+
+        let names = names
+            .into_iter()
+            .chain([name])
+            .sorted_by_key(|name| (*name != "self", *name))
+            .join(", ");
+        return Some(replace_lines(
+            statement.start,
+            statement.end + 1,
+            format!("{}{}::{{{}}};\n", statement.keyword, prefix, names),
+        ));
+    }
+    if statements.iter().any(|statement| statement.tree == path) {
+        return None;
+    }
+
+    let new_line = format!("use {};\n", path);
+    let kind = category(path);
+
+    // groups of statements that are not separated by blank lines
+    let groups =
+        statements
+            .iter()
+            .fold(Vec::<Vec<&UseStatement>>::new(), |mut groups, statement| {
+                match groups.last_mut() {
+                    Some(group) if group.last().is_some_and(|x| x.end + 1 == statement.start) => {
+                        group.push(statement);
+                    }
+                    _ => groups.push(Vec::from([statement])),
+                }
+                groups
+            });
+
+    // insert into the group of the same kind while keeping it sorted
+    if let Some(group) = groups.iter().find(|group| category(&group[0].tree) == kind) {
+        let line = group
+            .iter()
+            .find(|statement| statement.tree.as_str() > path)
+            .map_or(group[group.len() - 1].end + 1, |statement| statement.start);
+        return Some(replace_lines(line, line, new_line));
+    }
+
+    // otherwise start a new group before the first group of a later kind
+    if let Some(group) = groups.iter().find(|group| category(&group[0].tree) > kind) {
+        return Some(replace_lines(
+            group[0].start,
+            group[0].start,
+            format!("{new_line}\n"),
+        ));
+    }
+    if let Some(group) = groups.last() {
+        let line = group[group.len() - 1].end + 1;
+        return Some(replace_lines(line, line, format!("\n{new_line}")));
+    }
+
+    // the first import is placed after the module documentation and inner attributes
+    let line = lines
+        .iter()
+        .position(|line| {
+            let line = line.trim_start();
+            !(line.is_empty() || line.starts_with("//!") || line.starts_with("#!["))
+        })
+        .unwrap_or(lines.len());
+    Some(replace_lines(line, line, format!("{new_line}\n")))
+}
+
+/// collect all top level `use` statements, which are not indented
+fn use_statements(lines: &[&str]) -> Vec<UseStatement> {
+    let mut statements = Vec::new();
+    let mut lines = lines.iter().enumerate();
+
+    while let Some((start, line)) = lines.next() {
+        let Some(idx) = line.find("use ") else {
+            continue;
+        };
+        let keyword = &line[..idx + "use ".len()];
+        if !is_use_keyword(keyword) {
+            continue;
+        }
+
+        // a statement may span multiple lines until it is terminated by a semicolon
+        let mut tree = line[keyword.len()..].to_owned();
+        let mut end = start;
+        while !tree.contains(';') {
+            let Some((idx, line)) = lines.next() else {
+                break;
+            };
+            tree.push_str(line);
+            end = idx;
+        }
+
+        let tree = tree
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .split_whitespace()
+            .join(" ");
+        statements.push(UseStatement {
+            start,
+            end,
+            keyword: keyword.to_owned(),
+            tree,
+        });
+    }
+
+    statements
+}
+
+/// check if the start of a line is `use` with an optional visibility, such as `pub(crate) use `
+fn is_use_keyword(keyword: &str) -> bool {
+    let Some(rest) = keyword.strip_prefix("pub") else {
+        return keyword == "use ";
+    };
+    let rest = match rest.strip_prefix('(') {
+        Some(rest) => rest.split_once(')').map_or("", |(_, rest)| rest),
+        None => rest,
+    };
+    rest == " use "
+}
+
+/// the names imported by a use tree with the given prefix, such as `[HashMap, HashSet]` for
+/// `std::collections::{HashMap, HashSet}` or `[HashMap]` for `std::collections::HashMap`
+fn merged_names<'a>(tree: &'a str, prefix: &str) -> Option<Vec<&'a str>> {
+    let rest = tree.strip_prefix(prefix)?.strip_prefix("::")?;
+
+    if let Some(names) = rest.strip_prefix('{').and_then(|x| x.strip_suffix('}')) {
+        // nested trees are left untouched
+        if names.contains(['{', ':']) {
+            return None;
+        }
+        return Some(
+            names
+                .split(',')
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .collect(),
+        );
+    }
+
+    // a single name, renamed imports and globs cannot be merged
+    (!rest.contains([':', '*', '{']) && !rest.contains(" as ")).then(|| Vec::from([rest]))
+}
+
+/// the kind of an import, used to order groups of imports
+/// - 0: the standard library
+/// - 1: external crates
+/// - 2: the local crate
+fn category(tree: &str) -> u8 {
+    match tree.split("::").next().unwrap_or_default() {
+        "std" | "core" | "alloc" | "proc_macro" | "test" => 0,
+        "crate" | "self" | "super" => 2,
+        _ => 1,
+    }
+}
+
+/// replace the lines in the range `start..end` with the given text
+fn replace_lines(start: usize, end: usize, new_text: String) -> TextEdit {
+    #[expect(clippy::cast_possible_truncation)]
+    TextEdit {
+        range: Range {
+            start: Position {
+                line: start as u32,
+                character: 0,
+            },
+            end: Position {
+                line: end as u32,
+                character: 0,
+            },
+        },
+        new_text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the text after importing the path, edits replace whole lines
+    fn import(text: &str, path: &str) -> Option<String> {
+        let edit = insert_use(text, path)?;
+        let lines = text.split_inclusive('\n').collect::<Vec<_>>();
+        let start = edit.range.start.line as usize;
+        let end = edit.range.end.line as usize;
+        Some(format!(
+            "{}{}{}",
+            lines[..start].concat(),
+            edit.new_text,
+            lines[end..].concat()
+        ))
+    }
+
+    #[test]
+    fn merge() {
+        assert_eq!(
+            import(
+                "use std::collections::HashMap;\n",
+                "std::collections::HashSet"
+            )
+            .as_deref(),
+            Some("use std::collections::{HashMap, HashSet};\n")
+        );
+        assert_eq!(
+            import(
+                "pub(crate) use std::collections::{\n    HashSet,\n    BTreeMap,\n};\n\nfn main() {}\n",
+                "std::collections::HashMap"
+            )
+            .as_deref(),
+            Some("pub(crate) use std::collections::{BTreeMap, HashMap, HashSet};\n\nfn main() {}\n")
+        );
+        assert_eq!(
+            import("use std::fmt::{self, Display};\n", "std::fmt::Debug").as_deref(),
+            Some("use std::fmt::{self, Debug, Display};\n")
+        );
+        // renamed imports and globs are not merged into
+        assert_eq!(
+            import("use std::io::Result as IoResult;\n", "std::io::Read").as_deref(),
+            Some("use std::io::Read;\nuse std::io::Result as IoResult;\n")
+        );
+    }
+
+    #[test]
+    fn already_imported() {
+        assert_eq!(
+            import(
+                "use std::collections::HashMap;\n",
+                "std::collections::HashMap"
+            ),
+            None
+        );
+        assert_eq!(
+            import("use std::fmt::{Debug, Display};\n", "std::fmt::Display"),
+            None
+        );
+        assert_eq!(import("use std::io;\n", "std::io"), None);
+    }
+
+    #[test]
+    fn groups() {
+        let text =
+            "use std::fmt;\nuse std::io;\n\nuse serde::Deserialize;\n\nuse crate::Backend;\n";
+        assert_eq!(
+            import(text, "std::hash::Hash").as_deref(),
+            Some("use std::fmt;\nuse std::hash::Hash;\nuse std::io;\n\nuse serde::Deserialize;\n\nuse crate::Backend;\n")
+        );
+        assert_eq!(
+            import(text, "tokio::task").as_deref(),
+            Some("use std::fmt;\nuse std::io;\n\nuse serde::Deserialize;\nuse tokio::task;\n\nuse crate::Backend;\n")
+        );
+        // a kind without a group starts a new group before the later kinds
+        assert_eq!(
+            import("use crate::Backend;\n", "serde::Serialize").as_deref(),
+            Some("use serde::Serialize;\n\nuse crate::Backend;\n")
+        );
+        assert_eq!(
+            import("use std::fmt;\n\nfn main() {}\n", "crate::Backend").as_deref(),
+            Some("use std::fmt;\n\nuse crate::Backend;\n\nfn main() {}\n")
+        );
+    }
+
+    #[test]
+    fn first_import() {
+        assert_eq!(
+            import(
+                "//! docs\n#![allow(dead_code)]\n\nfn main() {}\n",
+                "std::env"
+            )
+            .as_deref(),
+            Some("//! docs\n#![allow(dead_code)]\n\nuse std::env;\n\nfn main() {}\n")
+        );
+        assert_eq!(import("", "std::env").as_deref(), Some("use std::env;\n\n"));
+        // indented statements belong to inner modules
+        assert_eq!(
+            import("mod a {\n    use std::fmt;\n}\n", "std::fmt").as_deref(),
+            Some("use std::fmt;\n\nmod a {\n    use std::fmt;\n}\n")
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! code related to the item table used for document outlines and workspace symbol search
+
+use std::collections::{HashMap, HashSet};
+
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher as _;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::*;
+
+#[derive(Debug, Default)]
+pub struct ItemTable {
+    /// invariant:
+    /// - the items in the same file are sorted by start position and then by end position in
+    ///   reverse, so that a parent always comes before its children
+    pub inner: HashMap<Url, Vec<Item>>,
+    /// files that belong to dependencies rather than workspace members
+    pub dependencies: HashSet<Url>,
+}
+
+impl ItemTable {
+    pub fn merge_replace(&mut self, other: Self) {
+        for (url, items) in other.inner {
+            self.inner.entry(url).insert_entry(items);
+        }
+        self.dependencies.extend(other.dependencies);
+    }
+
+    /// drop the results of the files that `keep` rejects, such as files that were deleted
+    pub fn retain_files(&mut self, keep: impl Fn(&Url) -> bool) {
+        self.inner.retain(|url, _| keep(url));
+        self.dependencies.retain(|url| keep(url));
+    }
+
+    /// retrieve the hierarchical outline of the given file
+    pub fn outline(&self, url: &Url) -> Option<Vec<DocumentSymbol>> {
+        self.inner.get(url).map(|items| outline(items))
+    }
+
+    /// find the item with the given full path
+    pub fn by_path(&self, path: &str) -> Option<(&Url, &Item)> {
+        self.inner.iter().find_map(|(url, items)| {
+            items
+                .iter()
+                .find(|item| item.path.as_deref() == Some(path))
+                .map(|item| (url, item))
+        })
+    }
+
+    /// fuzzy search the names of all items, or their full paths if the query contains `::`
+    /// the results are sorted from the best match to the worst match
+    pub fn search(&self, query: &str, include_dependencies: bool) -> Vec<(Url, &Item)> {
+        let matcher = SkimMatcherV2::default();
+        let by_path = query.contains("::");
+
+        let mut results = self
+            .inner
+            .iter()
+            .filter(|(url, _)| include_dependencies || !self.dependencies.contains(url))
+            .flat_map(|(url, items)| items.iter().map(move |item| (url, item)))
+            // impl blocks do not have a name that can be searched for
+            .filter(|(_, item)| item.kind != SymbolKind::OBJECT)
+            .filter_map(|(url, item)| {
+                let choice = match (&item.path, by_path) {
+                    (Some(path), true) => path,
+                    _ => &item.name,
+                };
+                let score = matcher.fuzzy_match(choice, query)?;
+                Some((score, url, item))
+            })
+            .collect::<Vec<_>>();
+        results.sort_unstable_by(|(a, ..), (b, ..)| b.cmp(a));
+
+        results
+            .into_iter()
+            .map(|(_, url, item)| (url.clone(), item))
+            .collect()
+    }
+}
+
+/// an item (module, struct, function, etc.) declared in a source file
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Item {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// additional information such as the signature of a function or the type of a field
+    pub detail: Option<String>,
+    /// full path of the item such as `crate::module::Type::method`
+    /// only available for items recorded by the embedded compiler
+    pub path: Option<String>,
+    /// range of the entire item, including its body
+    pub range: Range,
+    /// range of the identifier of the item
+    pub selection_range: Range,
+}
+
+/// sort the items to uphold the invariant of the item table and remove duplicates, which occur
+/// when the same file is compiled as part of multiple crates
+pub fn sort_items(items: &mut Vec<Item>) {
+    items.sort_unstable_by(|a, b| {
+        (a.range.start, b.range.end, &a.name).cmp(&(b.range.start, a.range.end, &b.name))
+    });
+    items.dedup();
+}
+
+/// nest the sorted items into a tree of document symbols using the containment of their ranges
+pub fn outline(items: &[Item]) -> Vec<DocumentSymbol> {
+    // stack of the parents of the current item, the last element is the innermost parent
+    let mut stack: Vec<DocumentSymbol> = Vec::new();
+    let mut roots = Vec::new();
+
+    for item in items {
+        // pop all parents that do not contain the current item
+        while let Some(parent) = stack.last() {
+            if parent.range.end >= item.range.end {
+                break;
+            }
+            let parent = stack.pop().expect("stack is not empty");
+            push_child(&mut stack, &mut roots, parent);
+        }
+
+        #[expect(deprecated)]
+        stack.push(DocumentSymbol {
+            name: item.name.clone(),
+            detail: item.detail.clone(),
+            kind: item.kind,
+            tags: None,
+            deprecated: None,
+            range: item.range,
+            selection_range: item.selection_range,
+            children: None,
+        });
+    }
+
+    while let Some(parent) = stack.pop() {
+        push_child(&mut stack, &mut roots, parent);
+    }
+
+    roots
+}
+
+/// add the symbol as a child of the innermost parent or as a root if there are no parents
+fn push_child(
+    stack: &mut [DocumentSymbol],
+    roots: &mut Vec<DocumentSymbol>,
+    symbol: DocumentSymbol,
+) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.get_or_insert_with(Vec::new).push(symbol),
+        None => roots.push(symbol),
+    }
+}
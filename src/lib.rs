@@ -1,28 +1,68 @@
 #![feature(rustc_private)]
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
+use cfg::CfgTable;
+use config::Config;
 use dashmap::DashMap;
 use ropey::Rope;
+use serde_json::Value;
 use tokio::sync::Mutex;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
 
+use edit::EditLog;
+use encoding::PositionEncoding;
+use highlight::HighlightTable;
+use implementation::ImplementationTable;
+use item::ItemTable;
 use lsp::diagnostic::QuickFix;
+use member::MemberTable;
+use runnable::RunnableTable;
+use signature::SignatureTable;
 use symbol::SymbolTable;
+use syntax::Syntax;
 
+mod cfg;
+mod config;
+mod edit;
+mod encoding;
+mod format;
+mod highlight;
+mod implementation;
+mod import;
+mod item;
 mod lsp;
+mod member;
+mod runnable;
 mod rustc;
+mod signature;
 mod symbol;
+mod syntax;
 
 /// TODO: add a oncelock field to retrieve the workspace root using `cargo metadata`
 #[derive(Debug)]
 struct Backend {
     /// handle to the editor client to send notifications and logs
     client: Client,
-    /// map of URIs to opened file contents
-    opened_files: DashMap<Url, Rope>,
+    /// encoding of the columns of positions, negotiated with the client during initialization
+    encoding: OnceLock<PositionEncoding>,
+    /// settings sent by the client during initialization and whenever they change
+    config: std::sync::Mutex<Config>,
+    /// whether the workspace was checked since the server started, the crates that are fresh in
+    /// the target directory only have results in the tables after the first check
+    checked: AtomicBool,
+    /// map of URIs to the version and contents of opened files
+    opened_files: DashMap<Url, (i32, Rope)>,
+    /// map of URIs to edits made to opened files since they were last saved
+    edits: DashMap<Url, EditLog>,
+    /// map of URIs to the results of parsing opened files, updated on every change
+    syntax: DashMap<Url, Syntax>,
+    /// map of URIs to the errors reported by rustfmt the last time it failed to format the file
+    format_errors: DashMap<Url, Vec<Diagnostic>>,
     /// map of URIs to list of diagnostics and quick fixes
     /// TODO: split into two maps:
     /// - files with diagnostics (makes it easy to clear diagnostics)
@@ -30,34 +70,229 @@ struct Backend {
     diagnostics: Mutex<HashMap<Url, Vec<(Diagnostic, QuickFix)>>>,
     /// symbols from the entire workspace
     symbols: std::sync::Mutex<SymbolTable>,
+    /// items from the entire workspace
+    items: std::sync::Mutex<ItemTable>,
+    /// members of types and modules from the entire workspace
+    members: std::sync::Mutex<MemberTable>,
+    /// signatures of functions and calls from the entire workspace
+    signatures: std::sync::Mutex<SignatureTable>,
+    /// implementations of traits and types from the entire workspace
+    implementations: std::sync::Mutex<ImplementationTable>,
+    /// occurrences of bindings and exit points of functions from the entire workspace
+    highlights: std::sync::Mutex<HighlightTable>,
+    /// cfg options of the compilations that included each file of the workspace
+    cfgs: std::sync::Mutex<CfgTable>,
+    /// tests, benches, main functions and doc tests from the entire workspace
+    runnables: std::sync::Mutex<RunnableTable>,
 }
 
 impl Backend {
     fn with_client(client: Client) -> Self {
         Self {
             client,
+            encoding: OnceLock::new(),
+            config: std::sync::Mutex::default(),
+            checked: AtomicBool::new(false),
             opened_files: DashMap::new(),
+            edits: DashMap::new(),
+            syntax: DashMap::new(),
+            format_errors: DashMap::new(),
             diagnostics: Mutex::default(),
             symbols: std::sync::Mutex::default(),
+            items: std::sync::Mutex::default(),
+            members: std::sync::Mutex::default(),
+            signatures: std::sync::Mutex::default(),
+            implementations: std::sync::Mutex::default(),
+            highlights: std::sync::Mutex::default(),
+            cfgs: std::sync::Mutex::default(),
+            runnables: std::sync::Mutex::default(),
         }
     }
+
+    /// the negotiated position encoding, which is UTF-16 until the client is initialized
+    fn encoding(&self) -> PositionEncoding {
+        self.encoding.get().copied().unwrap_or_default()
+    }
+
+    /// a copy of the current settings, so that the lock is not held while they are used
+    fn config(&self) -> Config {
+        self.config.lock().expect("poisoned").clone()
+    }
+
+    /// map a position in the opened document to the document at the time of the last check, which
+    /// the tables describe, returns `None` if the position is inside text inserted since then
+    fn position_to_checked(&self, uri: &Url, position: Position) -> Option<Position> {
+        match self.edits.get(uri) {
+            Some(edits) => edits.map_to_checked(position),
+            None => Some(position),
+        }
+    }
+
+    /// map a range from the tables to the opened document, returns `None` if either end is inside
+    /// text that was replaced since the last check
+    fn range_from_checked(&self, uri: &Url, range: Range) -> Option<Range> {
+        match self.edits.get(uri) {
+            Some(edits) => edits.map_range_from_checked(range),
+            None => Some(range),
+        }
+    }
+
+    fn location_from_checked(&self, Location { uri, range }: Location) -> Option<Location> {
+        let range = self.range_from_checked(&uri, range)?;
+        Some(Location { uri, range })
+    }
+
+    /// check the workspace with the bundled compiler and update the tables with the results
+    /// - cargo only compiles the crates that changed, so the results of the other crates are kept
+    ///   and only the results of deleted files are dropped
+    /// - `reload` compiles every crate again and replaces the tables, e.g. after a manifest or a
+    ///   setting of the check changed, so that files that are no longer part of the workspace are
+    ///   dropped as well
+    /// - cargo reads the manifests on every check, so new members are picked up either way
+    /// - the first check always reloads, since the tables are still empty
+    async fn check_workspace(&self, reload: bool) {
+        let reload = reload || !self.checked.load(Ordering::Relaxed);
+        // TODO: get the manifest path using `cargo metadata`
+        let analysis = match rustc::check_workspace(
+            &std::env::current_dir()
+                .expect("failed to get current directory")
+                .join("Cargo.toml"),
+            self.config(),
+            reload,
+        )
+        .await
+        {
+            Ok(analysis) => analysis,
+            Err(err) => {
+                self.client
+                    .log_message(
+                        MessageType::ERROR,
+                        format!("failed to check workspace: {err}"),
+                    )
+                    .await;
+                return;
+            }
+        };
+
+        for error in &analysis.errors {
+            self.client.log_message(MessageType::WARNING, error).await;
+        }
+        self.checked.store(true, Ordering::Relaxed);
+
+        if reload {
+            *self.symbols.lock().expect("poisoned") = analysis.symbols;
+            *self.items.lock().expect("poisoned") = analysis.items;
+            *self.members.lock().expect("poisoned") = analysis.members;
+            *self.signatures.lock().expect("poisoned") = analysis.signatures;
+            *self.implementations.lock().expect("poisoned") = analysis.implementations;
+            *self.highlights.lock().expect("poisoned") = analysis.highlights;
+            *self.cfgs.lock().expect("poisoned") = analysis.cfgs;
+            *self.runnables.lock().expect("poisoned") = analysis.runnables;
+        } else {
+            self.symbols
+                .lock()
+                .expect("poisoned")
+                .merge_replace(analysis.symbols);
+            self.items
+                .lock()
+                .expect("poisoned")
+                .merge_replace(analysis.items);
+            self.members
+                .lock()
+                .expect("poisoned")
+                .merge_replace(analysis.members);
+            self.signatures
+                .lock()
+                .expect("poisoned")
+                .merge_replace(analysis.signatures);
+            self.implementations
+                .lock()
+                .expect("poisoned")
+                .merge_replace(analysis.implementations);
+            self.highlights
+                .lock()
+                .expect("poisoned")
+                .merge_replace(analysis.highlights);
+            self.cfgs
+                .lock()
+                .expect("poisoned")
+                .merge_replace(analysis.cfgs);
+            self.runnables
+                .lock()
+                .expect("poisoned")
+                .merge_replace(analysis.runnables);
+
+            let exists = |url: &Url| url.to_file_path().is_ok_and(|path| path.exists());
+            self.symbols.lock().expect("poisoned").retain_files(exists);
+            self.items.lock().expect("poisoned").retain_files(exists);
+            self.members.lock().expect("poisoned").retain_files(exists);
+            self.signatures
+                .lock()
+                .expect("poisoned")
+                .retain_files(exists);
+            self.implementations
+                .lock()
+                .expect("poisoned")
+                .retain_files(exists);
+            self.highlights
+                .lock()
+                .expect("poisoned")
+                .retain_files(exists);
+            self.cfgs.lock().expect("poisoned").retain_files(exists);
+            self.runnables
+                .lock()
+                .expect("poisoned")
+                .retain_files(exists);
+        }
+
+        // the inactive code of the opened documents depends on the cfg options of the check
+        lsp::diagnostic::republish_diagnostics(self).await;
+    }
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
     /// current supported capabilities:
+    /// - position encoding negotiation (UTF-8, UTF-16 and UTF-32)
+    /// - configuration (registered dynamically)
     /// - text synchronization
     /// - formatting
+    /// - range formatting
+    /// - on type formatting
     /// - diagnostics
     /// - quick fixes
     /// - hover
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    /// - document symbols
+    /// - workspace symbols
+    /// - completion
+    /// - signature help
+    /// - go to implementation
+    /// - go to type definition
+    /// - call hierarchy
+    /// - type hierarchy (registered dynamically)
+    /// - watched files (registered dynamically)
+    /// - document highlight
+    /// - folding ranges
+    /// - selection ranges
+    /// - semantic tokens (inactive code)
+    /// - code lenses (runnables)
+    /// - execute command (running tests, benches, binaries and doc tests)
+    #[expect(clippy::too_many_lines)]
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let encoding = PositionEncoding::negotiate(&params.capabilities);
+        // the client is only initialized once, so the encoding cannot be set already
+        let _ = self.encoding.set(encoding);
+        if let Some(options) = params.initialization_options {
+            lsp::configuration::update_config(self, options).await;
+        }
+
         Ok(InitializeResult {
             server_info: Some(ServerInfo {
                 name: env!("CARGO_PKG_NAME").to_string(),
                 version: Some(env!("CARGO_PKG_VERSION").to_string()),
             }),
             capabilities: ServerCapabilities {
+                position_encoding: Some(encoding.kind()),
                 text_document_sync: Some(TextDocumentSyncCapability::Options(
                     TextDocumentSyncOptions {
                         open_close: Some(true),
@@ -74,6 +309,11 @@ impl LanguageServer for Backend {
                         work_done_progress: Some(false),
                     },
                 })),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "}".to_owned(),
+                    more_trigger_character: Some(Vec::from([";".to_owned(), "\n".to_owned()])),
+                }),
                 code_action_provider: Some(CodeActionProviderCapability::Options(
                     CodeActionOptions {
                         code_action_kinds: Some(Vec::from([CodeActionKind::QUICKFIX])),
@@ -88,6 +328,65 @@ impl LanguageServer for Backend {
                         work_done_progress: Some(false),
                     },
                 })),
+                document_symbol_provider: Some(OneOf::Right(DocumentSymbolOptions {
+                    label: None,
+                    work_done_progress_options: WorkDoneProgressOptions {
+                        work_done_progress: Some(false),
+                    },
+                })),
+                workspace_symbol_provider: Some(OneOf::Right(WorkspaceSymbolOptions {
+                    work_done_progress_options: WorkDoneProgressOptions {
+                        work_done_progress: Some(false),
+                    },
+                    resolve_provider: Some(false),
+                })),
+                completion_provider: Some(CompletionOptions {
+                    resolve_provider: Some(true),
+                    trigger_characters: Some(Vec::from([".".to_owned(), ":".to_owned()])),
+                    all_commit_characters: None,
+                    work_done_progress_options: WorkDoneProgressOptions {
+                        work_done_progress: Some(false),
+                    },
+                    completion_item: None,
+                }),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(Vec::from(["(".to_owned(), ",".to_owned()])),
+                    retrigger_characters: None,
+                    work_done_progress_options: WorkDoneProgressOptions {
+                        work_done_progress: Some(false),
+                    },
+                }),
+                implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
+                type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+                document_highlight_provider: Some(OneOf::Right(DocumentHighlightOptions {
+                    work_done_progress_options: WorkDoneProgressOptions {
+                        work_done_progress: Some(false),
+                    },
+                })),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            work_done_progress_options: WorkDoneProgressOptions {
+                                work_done_progress: Some(false),
+                            },
+                            legend: lsp::semantic_tokens::legend(),
+                            range: Some(false),
+                            full: Some(SemanticTokensFullOptions::Bool(true)),
+                        },
+                    ),
+                ),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: lsp::execute_command::commands(),
+                    work_done_progress_options: WorkDoneProgressOptions {
+                        work_done_progress: Some(false),
+                    },
+                }),
                 ..Default::default()
             },
         })
@@ -100,15 +399,18 @@ impl LanguageServer for Backend {
                 concat!("hello world from ", env!("CARGO_PKG_NAME")),
             )
             .await;
+        lsp::type_hierarchy::register_type_hierarchy(self).await;
+        lsp::watched_files::register_file_watchers(self).await;
+        lsp::configuration::register_configuration(self).await;
         lsp::diagnostic::handle_diagnostics(self).await;
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        lsp::file_sync::handle_did_open(self, params);
+        lsp::file_sync::handle_did_open(self, params).await;
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
-        lsp::file_sync::handle_did_close(self, &params);
+        lsp::file_sync::handle_did_close(self, params).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
@@ -119,26 +421,43 @@ impl LanguageServer for Backend {
     /// - a bundled rustc compiler is called to perform type checking
     /// - TODO: use the diagnostics from the bundled compiler instead of performing a separate
     /// cargo check call
-    async fn did_save(&self, _: DidSaveTextDocumentParams) {
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        // the edits are kept so that the results of the last check still follow the document
+        if !self.config().check_on_save {
+            return;
+        }
+        // the saved file on disk matches the opened file, which is what the check will see
+        self.edits.remove(&params.text_document.uri);
         lsp::diagnostic::handle_diagnostics(self).await;
-        // TODO: get the manifest path using `cargo metadata`
-        let results = rustc::check_workspace(
-            &std::env::current_dir()
-                .expect("failed to get current directory")
-                .join("Cargo.toml"),
-        )
-        .await
-        .expect("failed to check workspace");
-        self.symbols
-            .lock()
-            .expect("poisoned")
-            .merge_replace(results);
+        self.check_workspace(false).await;
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        lsp::configuration::handle_did_change_configuration(self, params).await;
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        lsp::watched_files::handle_did_change_watched_files(self, params).await;
     }
 
     async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
         lsp::format::handle_formatting(self, params).await
     }
 
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        lsp::format::handle_range_formatting(self, params).await
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        lsp::format::handle_on_type_formatting(self, params).await
+    }
+
     async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
         lsp::code_action::handle_code_action(self, params).await
     }
@@ -147,6 +466,129 @@ impl LanguageServer for Backend {
         Ok(lsp::hover::handle_hover(self, params))
     }
 
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        lsp::document_symbol::handle_document_symbol(self, params)
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        Ok(Some(lsp::workspace_symbol::handle_workspace_symbol(
+            self, &params,
+        )))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        lsp::completion::handle_completion(self, params)
+    }
+
+    async fn completion_resolve(&self, params: CompletionItem) -> Result<CompletionItem> {
+        Ok(lsp::completion::handle_completion_resolve(self, params))
+    }
+
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        lsp::signature_help::handle_signature_help(self, params)
+    }
+
+    async fn goto_implementation(
+        &self,
+        params: request::GotoImplementationParams,
+    ) -> Result<Option<request::GotoImplementationResponse>> {
+        Ok(lsp::implementation::handle_implementation(self, params))
+    }
+
+    async fn goto_type_definition(
+        &self,
+        params: request::GotoTypeDefinitionParams,
+    ) -> Result<Option<request::GotoTypeDefinitionResponse>> {
+        Ok(lsp::type_definition::handle_type_definition(self, params))
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        Ok(lsp::call_hierarchy::handle_prepare_call_hierarchy(
+            self, params,
+        ))
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        Ok(lsp::call_hierarchy::handle_incoming_calls(self, params))
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        Ok(lsp::call_hierarchy::handle_outgoing_calls(self, params))
+    }
+
+    async fn prepare_type_hierarchy(
+        &self,
+        params: TypeHierarchyPrepareParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        Ok(lsp::type_hierarchy::handle_prepare_type_hierarchy(
+            self, params,
+        ))
+    }
+
+    async fn supertypes(
+        &self,
+        params: TypeHierarchySupertypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        Ok(lsp::type_hierarchy::handle_supertypes(self, params))
+    }
+
+    async fn subtypes(
+        &self,
+        params: TypeHierarchySubtypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        Ok(lsp::type_hierarchy::handle_subtypes(self, params))
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        Ok(lsp::document_highlight::handle_document_highlight(
+            self, params,
+        ))
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        lsp::folding_range::handle_folding_range(self, params)
+    }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        lsp::selection_range::handle_selection_range(self, params)
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        lsp::semantic_tokens::handle_semantic_tokens_full(self, params)
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        Ok(lsp::code_lens::handle_code_lens(self, params))
+    }
+
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
+        lsp::execute_command::handle_execute_command(self, params).await
+    }
+
     async fn shutdown(&self) -> Result<()> {
         Ok(())
     }
@@ -1,8 +1,24 @@
 //! module to group LSP related code
 
+pub mod call_hierarchy;
 pub mod code_action;
+pub mod code_lens;
+pub mod completion;
+pub mod configuration;
 pub mod diagnostic;
+pub mod document_highlight;
+pub mod document_symbol;
 pub mod error;
+pub mod execute_command;
 pub mod file_sync;
+pub mod folding_range;
 pub mod format;
 pub mod hover;
+pub mod implementation;
+pub mod selection_range;
+pub mod semantic_tokens;
+pub mod signature_help;
+pub mod type_definition;
+pub mod type_hierarchy;
+pub mod watched_files;
+pub mod workspace_symbol;
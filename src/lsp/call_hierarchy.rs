@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+use tower_lsp::lsp_types::*;
+
+use crate::encoding::Converter;
+use crate::item::Item;
+use crate::Backend;
+
+/// find the function called or declared at the given position
+pub fn handle_prepare_call_hierarchy(
+    backend: &Backend,
+    CallHierarchyPrepareParams {
+        text_document_position_params:
+            TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+        ..
+    }: CallHierarchyPrepareParams,
+) -> Option<Vec<CallHierarchyItem>> {
+    let mut converter = Converter::new(backend);
+    let position = converter.decode(&uri, position);
+
+    // calls and items are only known for the document at the time of the last check
+    let position = backend.position_to_checked(&uri, position)?;
+
+    let items = backend.items.lock().expect("poisoned");
+    let signatures = backend.signatures.lock().expect("poisoned");
+    let path = match signatures.call_at(&uri, position) {
+        Some(call) => call.callee.as_str(),
+        None => items
+            .inner
+            .get(&uri)?
+            .iter()
+            .filter(|item| matches!(item.kind, SymbolKind::FUNCTION | SymbolKind::METHOD))
+            .find(|item| {
+                item.selection_range.start <= position && position <= item.selection_range.end
+            })?
+            .path
+            .as_deref()?,
+    };
+
+    let (uri, item) = items.by_path(path)?;
+    Some(Vec::from([hierarchy_item(
+        backend,
+        &mut converter,
+        uri,
+        item,
+        false,
+    )?]))
+}
+
+/// functions that call the given function
+pub fn handle_incoming_calls(
+    backend: &Backend,
+    CallHierarchyIncomingCallsParams { item, .. }: CallHierarchyIncomingCallsParams,
+) -> Option<Vec<CallHierarchyIncomingCall>> {
+    let Some(Value::String(path)) = item.data else {
+        return None;
+    };
+
+    let mut converter = Converter::new(backend);
+    let items = backend.items.lock().expect("poisoned");
+    let signatures = backend.signatures.lock().expect("poisoned");
+    let mut callers = BTreeMap::<&str, Vec<Range>>::new();
+    for (_, call) in signatures.incoming_calls(&path) {
+        callers.entry(&call.caller).or_default().push(call.range);
+    }
+
+    Some(
+        callers
+            .into_iter()
+            .filter_map(|(caller, from_ranges)| {
+                let (uri, item) = items.by_path(caller)?;
+                let from_ranges = encode_ranges(backend, &mut converter, uri, from_ranges)?;
+                Some(CallHierarchyIncomingCall {
+                    from: hierarchy_item(backend, &mut converter, uri, item, false)?,
+                    from_ranges,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// functions called by the given function
+pub fn handle_outgoing_calls(
+    backend: &Backend,
+    CallHierarchyOutgoingCallsParams { item, .. }: CallHierarchyOutgoingCallsParams,
+) -> Option<Vec<CallHierarchyOutgoingCall>> {
+    let caller_uri = item.uri;
+    let Some(Value::String(path)) = item.data else {
+        return None;
+    };
+
+    let mut converter = Converter::new(backend);
+    let items = backend.items.lock().expect("poisoned");
+    let signatures = backend.signatures.lock().expect("poisoned");
+    let mut callees = BTreeMap::<&str, (bool, Vec<Range>)>::new();
+    for call in signatures.outgoing_calls(&path) {
+        let (dynamic, from_ranges) = callees.entry(&call.callee).or_default();
+        *dynamic |= call.dynamic;
+        from_ranges.push(call.range);
+    }
+
+    // functions of crates that were not checked, such as the standard library, are not listed
+    Some(
+        callees
+            .into_iter()
+            .filter_map(|(callee, (dynamic, from_ranges))| {
+                let (uri, item) = items.by_path(callee)?;
+                // the calls are made in the file of the given function
+                let from_ranges = encode_ranges(backend, &mut converter, &caller_uri, from_ranges)?;
+                Some(CallHierarchyOutgoingCall {
+                    to: hierarchy_item(backend, &mut converter, uri, item, dynamic)?,
+                    from_ranges,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// map the ranges of the calls back to the edited document, calls in text that was edited since
+/// the last check are dropped, and `None` is returned if none are left
+fn encode_ranges(
+    backend: &Backend,
+    converter: &mut Converter<'_>,
+    uri: &Url,
+    ranges: Vec<Range>,
+) -> Option<Vec<Range>> {
+    let ranges: Vec<_> = ranges
+        .into_iter()
+        .filter_map(|range| backend.range_from_checked(uri, range))
+        .map(|range| converter.encode_range(uri, range))
+        .collect();
+    (!ranges.is_empty()).then_some(ranges)
+}
+
+/// - the path of the function is stored in the data of the item to look up its calls later
+/// - `None` if the function was edited since the last check
+fn hierarchy_item(
+    backend: &Backend,
+    converter: &mut Converter<'_>,
+    uri: &Url,
+    item: &Item,
+    dynamic: bool,
+) -> Option<CallHierarchyItem> {
+    // calls through a trait object may reach any implementation of the method
+    let detail = if dynamic {
+        Some(format!(
+            "{} (dynamic dispatch)",
+            item.detail.as_deref().unwrap_or_default()
+        ))
+    } else {
+        item.detail.clone()
+    };
+
+    let range = backend.range_from_checked(uri, item.range)?;
+    let selection_range = backend.range_from_checked(uri, item.selection_range)?;
+    Some(CallHierarchyItem {
+        name: item.name.clone(),
+        kind: item.kind,
+        tags: None,
+        detail,
+        uri: uri.clone(),
+        range: converter.encode_range(uri, range),
+        selection_range: converter.encode_range(uri, selection_range),
+        data: item.path.clone().map(Value::String),
+    })
+}
@@ -3,19 +3,30 @@ use std::collections::HashMap;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 
+use crate::encoding::Converter;
 use crate::lsp::diagnostic::{Applicability, QuickFix};
+use crate::lsp::error::CONTENT_MODIFIED;
+use crate::lsp::file_sync;
 use crate::Backend;
 
 #[allow(clippy::module_name_repetitions)]
 pub async fn handle_code_action(
-    Backend { diagnostics, .. }: &Backend,
+    backend: &Backend,
     CodeActionParams {
         text_document: TextDocumentIdentifier { uri },
         range,
         ..
     }: CodeActionParams,
 ) -> Result<Option<CodeActionResponse>> {
+    let version = file_sync::document_version(backend, &uri);
     let mut actions = Vec::new();
+    let mut converter = Converter::new(backend);
+    let range = converter.decode_range(&uri, range);
+
+    let diagnostics = backend.diagnostics.lock().await;
+    // the edits are only borrowed once the lock is held, so that changes are not blocked while
+    // waiting for a check to finish
+    let edits = backend.edits.get(&uri);
 
     // search through all diagnostics for the given file and range and add them to the actions vec
     // if there is a quick fix available (generated by cargo check)
@@ -29,26 +40,39 @@ pub async fn handle_code_action(
             suggested_replacement,
             suggestion_applicability,
         },
-    ) in diagnostics.lock().await.get(&uri).unwrap_or(&Vec::new())
+    ) in diagnostics.get(&uri).unwrap_or(&Vec::new())
     {
         // filter out diagnostics without suggested replacements
         // or that are not applicable to the current range
         let Some(replacement) = suggested_replacement else {
             continue;
         };
-        if !check_sub_range(*curr_range, range) {
+        // the fix is computed for the document at the time of the last check, fixes of text that
+        // was edited since then would overwrite the edits
+        let curr_range = match &edits {
+            Some(edits) => match edits.map_unedited_range_from_checked(*curr_range) {
+                Some(curr_range) => curr_range,
+                None => continue,
+            },
+            None => *curr_range,
+        };
+        if !check_sub_range(curr_range, range) {
             continue;
         }
+        let curr_range = converter.encode_range(&uri, curr_range);
 
         actions.push(CodeActionOrCommand::CodeAction(CodeAction {
             title: message.clone(),
             kind: Some(CodeActionKind::QUICKFIX),
-            diagnostics: Some(Vec::from([diagnostic.clone()])),
+            diagnostics: Some(Vec::from([Diagnostic {
+                range: curr_range,
+                ..diagnostic.clone()
+            }])),
             edit: Some(WorkspaceEdit {
                 changes: Some(HashMap::from([(
                     uri.clone(),
                     Vec::from([TextEdit {
-                        range: *curr_range,
+                        range: curr_range,
                         new_text: replacement.to_owned(),
                     }]),
                 )])),
@@ -65,6 +89,15 @@ pub async fn handle_code_action(
         }));
     }
 
+    drop(edits);
+    drop(diagnostics);
+
+    // the edits of the quick fixes would be applied at the wrong positions if the document was
+    // edited while waiting for the diagnostics
+    if file_sync::document_version(backend, &uri) != version {
+        return Err(CONTENT_MODIFIED);
+    }
+
     Ok(Some(actions))
 }
 
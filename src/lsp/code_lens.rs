@@ -0,0 +1,66 @@
+use tower_lsp::lsp_types::*;
+
+use crate::encoding::Converter;
+use crate::lsp::execute_command::{DEBUG, RUN};
+use crate::runnable::{Runnable, RunnableKind};
+use crate::Backend;
+
+/// - lenses are shown above the tests, benches, main functions and items with doc tests found by
+///   the last check, and run them with `workspace/executeCommand`
+/// - only tests can be debugged, since the client needs the test binary to attach a debugger
+pub fn handle_code_lens(
+    backend: &Backend,
+    CodeLensParams {
+        text_document: TextDocumentIdentifier { uri },
+        ..
+    }: CodeLensParams,
+) -> Option<Vec<CodeLens>> {
+    let runnables = backend
+        .runnables
+        .lock()
+        .expect("poisoned")
+        .inner
+        .get(&uri)
+        .cloned()?;
+    let edits = backend.edits.get(&uri);
+    let mut converter = Converter::new(backend);
+
+    let mut lenses = Vec::new();
+    for runnable in runnables {
+        // runnables whose start or end was edited since the check are dropped
+        let range = match &edits {
+            Some(edits) => match edits.map_range_from_checked(runnable.range) {
+                Some(range) => range,
+                None => continue,
+            },
+            None => runnable.range,
+        };
+        let range = converter.encode_range(&uri, range);
+
+        for (title, command) in commands(&runnable) {
+            lenses.push(CodeLens {
+                range,
+                command: Some(Command {
+                    title: title.to_owned(),
+                    command: command.to_owned(),
+                    arguments: Some(Vec::from([
+                        serde_json::to_value(&runnable).expect("failed to serialize")
+                    ])),
+                }),
+                data: None,
+            });
+        }
+    }
+
+    Some(lenses)
+}
+
+/// titles and commands of the lenses of the runnable
+fn commands(runnable: &Runnable) -> Vec<(&'static str, &'static str)> {
+    match runnable.kind {
+        RunnableKind::Test => Vec::from([("Run test", RUN), ("Debug test", DEBUG)]),
+        RunnableKind::Bench => Vec::from([("Run bench", RUN)]),
+        RunnableKind::Main => Vec::from([("Run", RUN)]),
+        RunnableKind::Doctest => Vec::from([("Run doctest", RUN)]),
+    }
+}
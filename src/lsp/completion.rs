@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::*;
+
+use crate::lsp::error::FILE_NOT_OPEN;
+use crate::member::{self, Member};
+use crate::{import, Backend};
+
+/// minimum length of a name before items that are not in scope are suggested
+const MIN_IMPORT_PREFIX: usize = 2;
+
+/// maximum number of items that are not in scope suggested at once
+const MAX_IMPORTS: usize = 64;
+
+/// data attached to completion items to retrieve their documentation when they are resolved
+#[derive(Debug, Serialize, Deserialize)]
+enum ResolveData {
+    TypeMember { ty: String, index: usize },
+    PathChild { path: String, index: usize },
+}
+
+#[allow(clippy::module_name_repetitions)]
+pub fn handle_completion(
+    backend: &Backend,
+    CompletionParams {
+        text_document_position:
+            TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+        ..
+    }: CompletionParams,
+) -> Result<Option<CompletionResponse>> {
+    let Some(document) = backend.opened_files.get(&uri) else {
+        return Err(FILE_NOT_OPEN);
+    };
+    let (_, document) = &*document;
+    let position = backend.encoding().decode_position(document, position);
+
+    // text of the current line before the cursor, without the partially typed name
+    let line = document.line(position.line as _);
+    let line_before = line
+        .slice(..(position.character as usize).min(line.len_chars()))
+        .to_string();
+    let before = line_before.trim_end_matches(is_ident_char);
+
+    let members = backend.members.lock().expect("poisoned");
+
+    // complete the children of a module, type or trait after `::`
+    if let Some(path) = before.strip_suffix("::") {
+        let start = path
+            .trim_end_matches(|c| is_ident_char(c) || c == ':')
+            .len();
+        let path = path[start..]
+            .trim_start_matches("crate::")
+            .trim_start_matches("self::")
+            .trim_start_matches("super::");
+        if path.is_empty() {
+            return Ok(None);
+        }
+
+        let Some((key, children)) = members.path_children(path) else {
+            return Ok(None);
+        };
+        let items = children
+            .iter()
+            .enumerate()
+            .map(|(index, child)| {
+                completion_item(
+                    child,
+                    &ResolveData::PathChild {
+                        path: key.to_owned(),
+                        index,
+                    },
+                )
+            })
+            .collect();
+        return Ok(Some(CompletionResponse::Array(items)));
+    }
+
+    // complete the fields and methods of the receiver after `.`
+    let Some(receiver) = before.strip_suffix('.') else {
+        // otherwise suggest items that are not in scope along with their import
+        let typed = &line_before[before.len()..];
+        drop(members);
+        return Ok(import_completions(
+            backend,
+            &uri,
+            &document.to_string(),
+            typed,
+        ));
+    };
+    if receiver.ends_with('.') || receiver.is_empty() {
+        return Ok(None);
+    }
+
+    // the receiver ends right before the `.`, which is mapped to the document of the last check
+    #[expect(clippy::cast_possible_truncation)]
+    let end = Position {
+        line: position.line,
+        character: receiver.chars().count() as u32,
+    };
+    let end = match backend.edits.get(&uri) {
+        Some(edits) => edits.map_to_checked(end),
+        None => Some(end),
+    };
+
+    // fall back to the type of the closest binding with the same name if the receiver expression
+    // was not part of the last check
+    let ty = end
+        .and_then(|end| members.expr_type(&uri, end))
+        .map(ToOwned::to_owned)
+        .or_else(|| {
+            let name = receiver.trim_end().rsplit(|c| !is_ident_char(c)).next()?;
+            let symbols = backend.symbols.lock().expect("poisoned");
+            let binding = symbols
+                .inner
+                .get(&uri)?
+                .iter()
+                .filter(|symbol| symbol.name == name)
+                .filter(|symbol| end.is_none_or(|end| symbol.range.end <= end))
+                .last()?;
+            Some(member::peel_refs(&binding.ty).to_owned())
+        });
+    let Some(ty) = ty else {
+        return Ok(None);
+    };
+    let Some(type_members) = members.types.get(&ty) else {
+        return Ok(None);
+    };
+
+    let items = type_members
+        .iter()
+        .enumerate()
+        .filter(|(_, member)| member.has_self || member.kind == CompletionItemKind::FIELD)
+        .map(|(index, member)| {
+            completion_item(
+                member,
+                &ResolveData::TypeMember {
+                    ty: ty.clone(),
+                    index,
+                },
+            )
+        })
+        .collect();
+    Ok(Some(CompletionResponse::Array(items)))
+}
+
+/// items of the crate of the file whose names start with the typed name, each with an edit that
+/// adds the `use` statement for it
+fn import_completions(
+    backend: &Backend,
+    uri: &Url,
+    text: &str,
+    typed: &str,
+) -> Option<CompletionResponse> {
+    if typed.chars().count() < MIN_IMPORT_PREFIX || typed.starts_with(char::is_numeric) {
+        return None;
+    }
+
+    // the crate of the file is the first segment of the path of any of its items
+    // names of the items in the file are excluded since they are already in scope
+    let (krate, local_names) = {
+        let items = backend.items.lock().expect("poisoned");
+        let file_items = items.inner.get(uri).map(Vec::as_slice).unwrap_or_default();
+        let krate = file_items
+            .iter()
+            .find_map(|item| item.path.as_deref()?.split("::").next())
+            .map(ToOwned::to_owned);
+        let local_names = file_items
+            .iter()
+            .map(|item| item.name.clone())
+            .collect::<Vec<_>>();
+        (krate, local_names)
+    };
+
+    let members = backend.members.lock().expect("poisoned");
+    let importables = match krate {
+        Some(krate) => members.importables.get(&krate)?,
+        // the crate is unambiguous if there is only one
+        None if members.importables.len() == 1 => members.importables.values().next()?,
+        None => return None,
+    };
+
+    let typed = typed.to_lowercase();
+    let items = importables
+        .iter()
+        .filter(|importable| importable.name.to_lowercase().starts_with(&typed))
+        .filter(|importable| !local_names.contains(&importable.name))
+        .filter_map(|importable| {
+            let edit = import::insert_use(text, &importable.path)?;
+            Some(CompletionItem {
+                label: importable.name.clone(),
+                label_details: Some(CompletionItemLabelDetails {
+                    detail: None,
+                    description: Some(importable.path.clone()),
+                }),
+                kind: Some(importable.kind),
+                detail: Some(importable.path.clone()),
+                additional_text_edits: Some(Vec::from([edit])),
+                ..Default::default()
+            })
+        })
+        .take(MAX_IMPORTS)
+        .collect();
+
+    // the list is incomplete since it only contains names starting with the typed name
+    Some(CompletionResponse::List(CompletionList {
+        is_incomplete: true,
+        items,
+    }))
+}
+
+/// add the documentation to a completion item
+pub fn handle_completion_resolve(backend: &Backend, mut item: CompletionItem) -> CompletionItem {
+    let Some(data) = item
+        .data
+        .take()
+        .and_then(|data| serde_json::from_value(data).ok())
+    else {
+        return item;
+    };
+
+    let members = backend.members.lock().expect("poisoned");
+    let member = match data {
+        ResolveData::TypeMember { ty, index } => members.types.get(&ty).and_then(|x| x.get(index)),
+        ResolveData::PathChild { path, index } => {
+            members.paths.get(&path).and_then(|x| x.get(index))
+        }
+    };
+
+    item.documentation =
+        member
+            .and_then(|member| member.documentation.clone())
+            .map(|documentation| {
+                Documentation::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: documentation,
+                })
+            });
+    item
+}
+
+fn completion_item(member: &Member, data: &ResolveData) -> CompletionItem {
+    CompletionItem {
+        label: member.name.clone(),
+        kind: Some(member.kind),
+        detail: member.detail.clone(),
+        data: serde_json::to_value(data).ok(),
+        ..Default::default()
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
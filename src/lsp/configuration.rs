@@ -0,0 +1,89 @@
+use serde_json::Value;
+use tower_lsp::lsp_types::*;
+
+use crate::config::{self, Config};
+use crate::lsp::diagnostic;
+use crate::Backend;
+
+/// clients only send the changed settings to servers that registered for them
+pub async fn register_configuration(backend: &Backend) {
+    let registration = Registration {
+        id: "workspace/didChangeConfiguration".to_owned(),
+        method: "workspace/didChangeConfiguration".to_owned(),
+        register_options: None,
+    };
+
+    if let Err(err) = backend
+        .client
+        .register_capability(Vec::from([registration]))
+        .await
+    {
+        backend
+            .client
+            .log_message(
+                MessageType::WARNING,
+                format!("failed to register the configuration: {err}"),
+            )
+            .await;
+    }
+}
+
+/// - clients may send the notification without any settings, in which case they are requested
+///   with `workspace/configuration`
+/// - the workspace is checked again if the settings of the check changed, otherwise the
+///   diagnostics are only published again
+pub async fn handle_did_change_configuration(
+    backend: &Backend,
+    DidChangeConfigurationParams { settings }: DidChangeConfigurationParams,
+) {
+    let settings = match settings {
+        Value::Null => {
+            let items = Vec::from([ConfigurationItem {
+                scope_uri: None,
+                section: Some(config::SECTION.to_owned()),
+            }]);
+            match backend.client.configuration(items).await {
+                Ok(mut settings) if !settings.is_empty() => settings.swap_remove(0),
+                Ok(_) => Value::Null,
+                Err(err) => {
+                    backend
+                        .client
+                        .log_message(
+                            MessageType::WARNING,
+                            format!("failed to request the configuration: {err}"),
+                        )
+                        .await;
+                    return;
+                }
+            }
+        }
+        settings => settings,
+    };
+
+    let Some(previous) = update_config(backend, settings).await else {
+        return;
+    };
+    if backend.config().check_changed(&previous) {
+        diagnostic::handle_diagnostics(backend).await;
+        backend.check_workspace(true).await;
+    } else {
+        diagnostic::republish_diagnostics(backend).await;
+    }
+}
+
+/// replace the settings and return the previous ones, invalid settings are reported and ignored
+pub async fn update_config(backend: &Backend, settings: Value) -> Option<Config> {
+    match Config::parse(settings) {
+        Ok(config) => Some(std::mem::replace(
+            &mut *backend.config.lock().expect("poisoned"),
+            config,
+        )),
+        Err(err) => {
+            backend
+                .client
+                .show_message(MessageType::ERROR, format!("invalid settings: {err}"))
+                .await;
+            None
+        }
+    }
+}
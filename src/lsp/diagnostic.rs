@@ -1,10 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 use serde::Deserialize;
 use tokio::process::Command;
 use tower_lsp::lsp_types::*;
 
+use crate::encoding::Converter;
 use crate::Backend;
 
 #[derive(Debug, Deserialize)]
@@ -55,7 +56,7 @@ struct Code {
 }
 
 pub async fn handle_diagnostics(
-    Backend {
+    backend @ Backend {
         client,
         diagnostics,
         ..
@@ -64,28 +65,38 @@ pub async fn handle_diagnostics(
     // NOTE: consider using the cargo-manifest crate
     // https://doc.rust-lang.org/cargo/reference/external-tools.html#json-messages
 
-    // lock mutex during the entire function
-    let mut diagnostics = diagnostics.lock().await;
-
     // remove all existing diagnostics
+    // the mutex is not held while cargo runs, so that documents edited meanwhile can still publish
+    // their syntax errors
     let remove_diagnostics = async {
+        let mut diagnostics = diagnostics.lock().await;
         for (document, _) in diagnostics.drain() {
             client.publish_diagnostics(document, Vec::new(), None).await;
         }
     };
 
-    // run clippy to get workspace diagnostics
+    // run clippy or cargo check to get workspace diagnostics
     // TODO: replace with internal rustc compiler
-    // TODO: use plain cargo check to increase speed
-    let output = Command::new("cargo")
-        .args(["clippy", "--workspace", "--message-format", "json"])
-        .output();
+    let config = backend.config();
+    let mut command = Command::new("cargo");
+    command
+        .arg(if config.clippy { "clippy" } else { "check" })
+        .args(["--workspace", "--message-format", "json"])
+        .args(config.cargo.cargo_args());
+    if config.all_targets {
+        command.arg("--all-targets");
+    }
+    if let Some(flags) = config.encoded_rustflags() {
+        command.env("CARGO_ENCODED_RUSTFLAGS", flags);
+    }
+    let output = command.output();
 
     // run both tasks concurrently
     let ((), output) = tokio::join!(remove_diagnostics, output);
 
     let output = output.expect("failed to get output from clippy");
     let output = String::from_utf8(output.stdout).expect("clippy output was not valid utf-8");
+    let mut checked = HashMap::new();
     let mut errors = Vec::new();
 
     // queue up all diagnostics
@@ -100,7 +111,7 @@ pub async fn handle_diagnostics(
             .manifest_path
             .parent()
             .expect("expected parent of Cargo.toml");
-        parse_diagnostics(src_root, &mut diagnostics, &mut errors, diagnostic.message);
+        parse_diagnostics(src_root, &mut checked, &mut errors, diagnostic.message);
 
         // log all errors
         for error in errors.drain(..) {
@@ -108,16 +119,111 @@ pub async fn handle_diagnostics(
         }
     }
 
-    // publish all diagnostics
-    for (uri, diagnostics) in diagnostics.iter() {
-        client
-            .publish_diagnostics(
-                uri.clone(),
-                diagnostics.iter().map(|d| d.0.clone()).collect(),
-                None,
-            )
-            .await;
+    let mut diagnostics = diagnostics.lock().await;
+    *diagnostics = checked;
+
+    publish_all(backend, &diagnostics).await;
+}
+
+/// publish the diagnostics of every document again, after the settings that filter them changed
+pub async fn republish_diagnostics(backend: &Backend) {
+    let diagnostics = backend.diagnostics.lock().await;
+    publish_all(backend, &diagnostics).await;
+}
+
+/// publish all diagnostics, including the syntax errors of documents edited since the check and
+/// the inactive code of opened documents
+async fn publish_all(backend: &Backend, diagnostics: &HashMap<Url, Vec<(Diagnostic, QuickFix)>>) {
+    let uris = diagnostics
+        .keys()
+        .cloned()
+        .chain(backend.edits.iter().map(|entry| entry.key().clone()))
+        .chain(backend.opened_files.iter().map(|entry| entry.key().clone()))
+        .chain(
+            backend
+                .format_errors
+                .iter()
+                .map(|entry| entry.key().clone()),
+        )
+        .collect::<HashSet<_>>();
+    for uri in uris {
+        let merged = merged_diagnostics(backend, diagnostics, &uri);
+        backend.client.publish_diagnostics(uri, merged, None).await;
+    }
+}
+
+/// publish the diagnostics of a document after it was edited or closed
+pub async fn publish_document_diagnostics(backend: &Backend, uri: Url) {
+    let diagnostics = backend.diagnostics.lock().await;
+    let merged = merged_diagnostics(backend, &diagnostics, &uri);
+    drop(diagnostics);
+
+    backend.client.publish_diagnostics(uri, merged, None).await;
+}
+
+/// diagnostics of the last check mapped to the current document, merged with the syntax errors of
+/// the current document if it was edited since the check, the errors of the last rustfmt run and
+/// the inactive code of the opened document
+/// diagnostics inside text that was replaced since the check are dropped
+fn merged_diagnostics(
+    backend: &Backend,
+    diagnostics: &HashMap<Url, Vec<(Diagnostic, QuickFix)>>,
+    uri: &Url,
+) -> Vec<Diagnostic> {
+    let config = backend.config();
+    let checked = diagnostics
+        .get(uri)
+        .into_iter()
+        .flatten()
+        .map(|x| &x.0)
+        .filter(|diagnostic| config.hints.shows(diagnostic.severity));
+    let mut merged = match backend.edits.get(uri) {
+        Some(edits) => {
+            let mut merged = checked
+                .filter_map(|diagnostic| {
+                    Some(Diagnostic {
+                        range: edits.map_range_from_checked(diagnostic.range)?,
+                        ..diagnostic.clone()
+                    })
+                })
+                .collect::<Vec<_>>();
+            if let Some(syntax) = backend
+                .syntax
+                .get(uri)
+                .filter(|_| config.syntax_errors_on_change)
+            {
+                merged.extend(syntax.errors.iter().cloned());
+            }
+            merged
+        }
+        None => checked.cloned().collect(),
+    };
+    // errors of rustfmt are located in the current document
+    if let Some(format_errors) = backend.format_errors.get(uri) {
+        merged.extend(format_errors.iter().cloned());
+    }
+    // the cfg attributes are parsed from the current document
+    if let Some(syntax) = backend.syntax.get(uri).filter(|_| config.hints.inactive) {
+        let cfgs = backend.cfgs.lock().expect("poisoned");
+        merged.extend(
+            cfgs.inactive_regions(uri, &syntax.cfg_regions)
+                .into_iter()
+                .map(|region| Diagnostic {
+                    range: region.range,
+                    severity: Some(DiagnosticSeverity::HINT),
+                    source: Some(env!("CARGO_PKG_NAME").to_string()),
+                    message: format!("inactive: cfg({}) is not enabled", region.predicate),
+                    tags: Some(Vec::from([DiagnosticTag::UNNECESSARY])),
+                    ..Default::default()
+                }),
+        );
+    }
+
+    let mut converter = Converter::new(backend);
+    for diagnostic in &mut merged {
+        diagnostic.range = converter.encode_range(uri, diagnostic.range);
     }
+    merged
 }
 
 fn parse_diagnostics(
@@ -157,20 +263,22 @@ fn parse_diagnostics(
             message.push_str(&format!("\n{}", label));
         }
 
-        diagnostics.entry(uri).or_default().push((
-            Diagnostic {
-                range,
-                severity,
-                code: code.clone(),
-                code_description: None,
-                source: Some(env!("CARGO_PKG_NAME").to_string()),
-                message,
-                related_information: None,
-                tags: None,
-                data: None,
-            },
-            span.quick_fix,
-        ));
+        let diagnostic = Diagnostic {
+            range,
+            severity,
+            code: code.clone(),
+            code_description: None,
+            source: Some(env!("CARGO_PKG_NAME").to_string()),
+            message,
+            related_information: None,
+            tags: None,
+            data: None,
+        };
+        // files compiled both as a library and as tests report the same diagnostics twice
+        let document = diagnostics.entry(uri).or_default();
+        if document.iter().all(|(existing, _)| *existing != diagnostic) {
+            document.push((diagnostic, span.quick_fix));
+        }
     }
 
     for child in message.children {
@@ -0,0 +1,55 @@
+use tower_lsp::lsp_types::*;
+
+use crate::encoding::Converter;
+use crate::Backend;
+
+pub fn handle_document_highlight(
+    backend: &Backend,
+    DocumentHighlightParams {
+        text_document_position_params:
+            TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+        ..
+    }: DocumentHighlightParams,
+) -> Option<Vec<DocumentHighlight>> {
+    let mut converter = Converter::new(backend);
+    let position = converter.decode(&uri, position);
+    let edits = backend.edits.get(&uri);
+
+    // highlights are computed for the document at the time of the last check
+    let position = match &edits {
+        Some(edits) => edits.map_to_checked(position)?,
+        None => position,
+    };
+    let highlights = backend
+        .highlights
+        .lock()
+        .expect("poisoned")
+        .highlights(&uri, position)?;
+
+    // occurrences in text that was edited since then are dropped
+    let highlights: Vec<_> = match edits {
+        Some(edits) => highlights
+            .into_iter()
+            .filter_map(|highlight| {
+                Some(DocumentHighlight {
+                    range: edits.map_range_from_checked(highlight.range)?,
+                    ..highlight
+                })
+            })
+            .collect(),
+        None => highlights,
+    };
+
+    Some(
+        highlights
+            .into_iter()
+            .map(|highlight| DocumentHighlight {
+                range: converter.encode_range(&uri, highlight.range),
+                ..highlight
+            })
+            .collect(),
+    )
+}
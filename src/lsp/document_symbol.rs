@@ -0,0 +1,49 @@
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::*;
+
+use crate::encoding::Converter;
+use crate::lsp::error::FILE_NOT_OPEN;
+use crate::{item, Backend};
+
+#[allow(clippy::module_name_repetitions)]
+pub fn handle_document_symbol(
+    backend: &Backend,
+    DocumentSymbolParams {
+        text_document: TextDocumentIdentifier { uri },
+        ..
+    }: DocumentSymbolParams,
+) -> Result<Option<DocumentSymbolResponse>> {
+    // prefer the items recorded by the embedded compiler since they carry type information, unless
+    // the document was edited since the last check and their ranges are outdated
+    if !backend.edits.contains_key(&uri) {
+        let outline = backend.items.lock().expect("poisoned").outline(&uri);
+        if let Some(mut outline) = outline {
+            encode_symbols(&mut Converter::new(backend), &uri, &mut outline);
+            return Ok(Some(DocumentSymbolResponse::Nested(outline)));
+        }
+    }
+
+    // fall back to a syntax-only parse, which follows every change
+    let Some(syntax) = backend.syntax.get(&uri) else {
+        return Err(FILE_NOT_OPEN);
+    };
+    let Some(items) = &syntax.items else {
+        return Ok(None);
+    };
+    let mut outline = item::outline(items);
+    drop(syntax);
+    encode_symbols(&mut Converter::new(backend), &uri, &mut outline);
+
+    Ok(Some(DocumentSymbolResponse::Nested(outline)))
+}
+
+/// convert the ranges of the symbols and their children to the negotiated encoding
+fn encode_symbols(converter: &mut Converter<'_>, uri: &Url, symbols: &mut [DocumentSymbol]) {
+    for symbol in symbols {
+        symbol.range = converter.encode_range(uri, symbol.range);
+        symbol.selection_range = converter.encode_range(uri, symbol.selection_range);
+        if let Some(children) = &mut symbol.children {
+            encode_symbols(converter, uri, children);
+        }
+    }
+}
@@ -7,6 +7,7 @@ use tower_lsp::jsonrpc::{Error, ErrorCode};
 pub enum Code {
     FileNotOpen = 1,
     RustfmtFailed,
+    CargoFailed,
 }
 
 pub const FILE_NOT_OPEN: Error = Error {
@@ -15,10 +16,36 @@ pub const FILE_NOT_OPEN: Error = Error {
     data: None,
 };
 
-pub fn rustfmt_failed(status: ExitStatus) -> Error {
+/// the document was edited while a response was computed for an earlier version of it
+pub const CONTENT_MODIFIED: Error = Error {
+    code: ErrorCode::ContentModified,
+    message: Cow::Borrowed("document changed while the request was handled"),
+    data: None,
+};
+
+/// the reason is the first error reported by rustfmt, if any, and there is no status if rustfmt
+/// could not be run at all
+pub fn rustfmt_failed(status: Option<ExitStatus>, reason: Option<&str>) -> Error {
+    let message = match (status, reason) {
+        (Some(status), Some(reason)) => {
+            format!("rustfmt failed with status {}: {}", status, reason)
+        }
+        (Some(status), None) => format!("rustfmt failed with status {}", status),
+        (None, reason) => reason.unwrap_or("failed to run rustfmt").to_owned(),
+    };
+
     Error {
         code: ErrorCode::ServerError(Code::RustfmtFailed as _),
-        message: Cow::Owned(format!("rustfmt failed with status {}", status)),
+        message: Cow::Owned(message),
+        data: None,
+    }
+}
+
+/// the cargo command of a runnable could not be run, or did not produce what was needed
+pub fn cargo_failed(command: &str, reason: &str) -> Error {
+    Error {
+        code: ErrorCode::ServerError(Code::CargoFailed as _),
+        message: Cow::Owned(format!("`{}` failed: {}", command, reason)),
         data: None,
     }
 }
@@ -0,0 +1,168 @@
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::process::Command;
+use tower_lsp::jsonrpc::{Error, Result};
+use tower_lsp::lsp_types::*;
+
+use crate::lsp::error;
+use crate::runnable::{Runnable, RunnableKind};
+use crate::Backend;
+
+/// run a test, bench, binary or doc test with cargo
+pub const RUN: &str = "minira.run";
+/// build a test and return how to launch it, for the client to run it under a debugger
+pub const DEBUG: &str = "minira.debug";
+
+pub fn commands() -> Vec<String> {
+    Vec::from([RUN.to_owned(), DEBUG.to_owned()])
+}
+
+/// message printed by `cargo test --no-run --message-format json` for each compiled target
+#[derive(Debug, Deserialize)]
+struct Artifact {
+    reason: String,
+    executable: Option<PathBuf>,
+}
+
+/// both commands take the runnable of the code lens as their only argument
+pub async fn handle_execute_command(
+    backend: &Backend,
+    ExecuteCommandParams {
+        command, arguments, ..
+    }: ExecuteCommandParams,
+) -> Result<Option<Value>> {
+    let Some(Ok(runnable)) = arguments
+        .into_iter()
+        .next()
+        .map(serde_json::from_value::<Runnable>)
+    else {
+        return Err(Error::invalid_params("expected a runnable as the argument"));
+    };
+
+    match command.as_str() {
+        RUN => run(backend, &runnable).await,
+        DEBUG => debug(backend, &runnable).await,
+        _ => Err(Error::invalid_params(format!(
+            "unknown command `{command}`"
+        ))),
+    }
+}
+
+/// - the runnable is run with the features and target of the check, and its output is logged
+/// - the request returns once cargo is started, since tests and binaries may run for a long time
+/// - the result is shown to the user when cargo exits, a failing test is not an error of the
+///   request
+async fn run(backend: &Backend, runnable: &Runnable) -> Result<Option<Value>> {
+    let config = backend.config();
+    let skipped = backend
+        .runnables
+        .lock()
+        .expect("poisoned")
+        .doctests_containing(runnable);
+    let args = runnable.cargo_args(&config.cargo, &skipped);
+    let command_line = format!("cargo {}", args.join(" "));
+    backend
+        .client
+        .log_message(MessageType::INFO, format!("running `{command_line}`"))
+        .await;
+
+    let mut command = Command::new("cargo");
+    command
+        .args(&args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(flags) = config.encoded_rustflags() {
+        command.env("CARGO_ENCODED_RUSTFLAGS", flags);
+    }
+    let child = command
+        .spawn()
+        .map_err(|err| error::cargo_failed(&command_line, &err.to_string()))?;
+
+    let client = backend.client.clone();
+    tokio::spawn(async move {
+        let output = match child.wait_with_output().await {
+            Ok(output) => output,
+            Err(err) => {
+                client
+                    .show_message(
+                        MessageType::ERROR,
+                        format!("`{command_line}` failed: {err}"),
+                    )
+                    .await;
+                return;
+            }
+        };
+
+        // cargo reports the build on stderr, while the tests and binaries print to stdout
+        for stream in [&output.stderr, &output.stdout] {
+            let text = String::from_utf8_lossy(stream);
+            if !text.trim().is_empty() {
+                client.log_message(MessageType::LOG, text).await;
+            }
+        }
+
+        let (kind, result) = if output.status.success() {
+            (MessageType::INFO, "succeeded")
+        } else {
+            (MessageType::ERROR, "failed")
+        };
+        client
+            .show_message(kind, format!("`{command_line}` {result}"))
+            .await;
+    });
+
+    Ok(None)
+}
+
+/// build the test binary without running it, and return the program, arguments and working
+/// directory that run only the test, which the client passes to its debugger
+async fn debug(backend: &Backend, runnable: &Runnable) -> Result<Option<Value>> {
+    if runnable.kind != RunnableKind::Test {
+        return Err(Error::invalid_params("only tests can be debugged"));
+    }
+
+    let config = backend.config();
+    let mut args = runnable.build_args(&config.cargo);
+    args.extend(["--no-run", "--message-format", "json"].map(str::to_owned));
+    let command_line = format!("cargo {}", args.join(" "));
+
+    let mut command = Command::new("cargo");
+    command.args(&args);
+    if let Some(flags) = config.encoded_rustflags() {
+        command.env("CARGO_ENCODED_RUSTFLAGS", flags);
+    }
+    let output = command
+        .output()
+        .await
+        .map_err(|err| error::cargo_failed(&command_line, &err.to_string()))?;
+    if !output.status.success() {
+        return Err(error::cargo_failed(
+            &command_line,
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ));
+    }
+
+    // the target selects a single test binary, dependencies do not have executables
+    let Some(program) = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Artifact>(line).ok())
+        .filter(|artifact| artifact.reason == "compiler-artifact")
+        .find_map(|artifact| artifact.executable)
+    else {
+        return Err(error::cargo_failed(
+            &command_line,
+            "no test binary was built",
+        ));
+    };
+
+    let mut test_args = runnable.test_args();
+    test_args.push("--nocapture".to_owned());
+    Ok(Some(json!({
+        "program": program,
+        "args": test_args,
+        "cwd": std::env::current_dir().ok(),
+    })))
+}
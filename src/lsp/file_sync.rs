@@ -1,60 +1,185 @@
 use ropey::Rope;
 use tower_lsp::lsp_types::*;
 
-use crate::Backend;
+use crate::lsp::diagnostic;
+use crate::{syntax, Backend};
 
-pub fn handle_did_open(
+pub async fn handle_did_open(
     backend: &Backend,
     DidOpenTextDocumentParams {
-        text_document: TextDocumentItem { uri, text, .. },
+        text_document: TextDocumentItem {
+            uri, version, text, ..
+        },
     }: DidOpenTextDocumentParams,
 ) {
-    backend.opened_files.insert(uri, Rope::from(text));
+    backend.edits.remove(&uri);
+    backend.syntax.insert(uri.clone(), syntax::parse(&text));
+    backend
+        .opened_files
+        .insert(uri.clone(), (version, Rope::from(text)));
+    diagnostic::publish_document_diagnostics(backend, uri).await;
 }
 
-pub fn handle_did_close(backend: &Backend, params: &DidCloseTextDocumentParams) {
-    backend.opened_files.remove(&params.text_document.uri);
+/// the diagnostics of the document are published again without its syntax errors, rustfmt errors
+/// and inactive code, which are only reported while it is open
+pub async fn handle_did_close(backend: &Backend, params: DidCloseTextDocumentParams) {
+    let uri = params.text_document.uri;
+    forget_document(backend, &uri);
+    diagnostic::publish_document_diagnostics(backend, uri).await;
 }
 
+/// why the document of the server no longer matches the document of the client
+enum Desync {
+    NotOpen,
+    /// a change was missed or received out of order
+    Version {
+        current: i32,
+        received: i32,
+    },
+    /// a change is outside of the document
+    Range(Range),
+}
+
+impl std::fmt::Display for Desync {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotOpen => write!(f, "the document is not open"),
+            Self::Version { current, received } => write!(
+                f,
+                "received version {received} after version {current} of the document"
+            ),
+            Self::Range(range) => write!(
+                f,
+                "the change at {}:{}-{}:{} is outside of the document",
+                range.start.line, range.start.character, range.end.line, range.end.character
+            ),
+        }
+    }
+}
+
+/// - changes are either incremental or replace the whole document
+/// - if a change cannot be applied, the document is dropped and the client is asked to send it
+///   again, which it does when the document is reopened or entirely replaced
 pub async fn handle_did_change(
     backend: &Backend,
     DidChangeTextDocumentParams {
-        text_document: VersionedTextDocumentIdentifier { uri, .. },
+        text_document: VersionedTextDocumentIdentifier { uri, version },
         content_changes,
     }: DidChangeTextDocumentParams,
 ) {
-    let Some(mut document) = backend.opened_files.get_mut(&uri) else {
-        backend
-            .client
-            .log_message(MessageType::WARNING, "document not open")
-            .await;
-        return;
-    };
-
-    for TextDocumentContentChangeEvent { range, text, .. } in content_changes {
-        let Some(Range {
-            start:
-                Position {
-                    line: line_start,
-                    character: character_start,
-                },
-            end:
-                Position {
-                    line: line_end,
-                    character: character_end,
-                },
-        }) = range
-        else {
+    let text = match apply_changes(backend, &uri, version, content_changes) {
+        Ok(text) => text,
+        Err(desync) => {
+            forget_document(backend, &uri);
             backend
                 .client
-                .log_message(MessageType::ERROR, "expected incremental change range")
+                .log_message(
+                    MessageType::ERROR,
+                    format!("{uri} is out of sync: {desync}"),
+                )
+                .await;
+            backend
+                .client
+                .show_message(
+                    MessageType::WARNING,
+                    format!("{uri} is out of sync with the server, reopen it to resynchronize"),
+                )
                 .await;
             return;
+        }
+    };
+
+    // the document is parsed again on every change to report syntax errors immediately, which
+    // replace the errors of the last rustfmt run since their positions are outdated
+    backend.format_errors.remove(&uri);
+    backend.syntax.insert(uri.clone(), syntax::parse(&text));
+    diagnostic::publish_document_diagnostics(backend, uri).await;
+}
+
+/// apply the changes to the document and return its new text
+fn apply_changes(
+    backend: &Backend,
+    uri: &Url,
+    version: i32,
+    changes: Vec<TextDocumentContentChangeEvent>,
+) -> Result<String, Desync> {
+    // replacing the whole document resynchronizes a document that was dropped
+    let replaced = changes.iter().any(|change| change.range.is_none());
+    let mut document = match backend.opened_files.get_mut(uri) {
+        Some(document) => document,
+        None if replaced => backend
+            .opened_files
+            .entry(uri.clone())
+            .or_insert((version, Rope::new())),
+        None => return Err(Desync::NotOpen),
+    };
+    let (current, document) = &mut *document;
+
+    // versions increase with every change but are not necessarily consecutive, so only a version
+    // that does not increase shows that a change was missed or reordered
+    if version <= *current && !replaced {
+        return Err(Desync::Version {
+            current: *current,
+            received: version,
+        });
+    }
+
+    let encoding = backend.encoding();
+    for TextDocumentContentChangeEvent { range, text, .. } in changes {
+        let Some(range) = range else {
+            *document = Rope::from(text.as_str());
+            backend
+                .edits
+                .entry(uri.clone())
+                .or_default()
+                .push(None, &text);
+            continue;
         };
+        // a column past the end of its line would edit the next line, so it is rejected rather
+        // than clamped like the positions of requests
+        let in_line = |position: Position| {
+            let line = document.line(position.line as usize);
+            position.character <= encoding.encode_column(line, u32::MAX)
+        };
+        if range.start > range.end
+            || range.end.line as usize >= document.len_lines()
+            || !in_line(range.start)
+            || !in_line(range.end)
+        {
+            return Err(Desync::Range(range));
+        }
 
-        let start = document.line_to_char(line_start as _) + character_start as usize;
-        let end = document.line_to_char(line_end as _) + character_end as usize;
+        // the columns of the change count code units of the negotiated encoding, which are
+        // converted to chars before the rope is edited
+        let range = Range {
+            start: encoding.decode_position(document, range.start),
+            end: encoding.decode_position(document, range.end),
+        };
+
+        let start = document.line_to_char(range.start.line as _) + range.start.character as usize;
+        let end = document.line_to_char(range.end.line as _) + range.end.character as usize;
         document.remove(start..end);
         document.insert(start, &text);
+        backend
+            .edits
+            .entry(uri.clone())
+            .or_default()
+            .push(Some(range), &text);
     }
+    *current = version;
+
+    Ok(document.to_string())
+}
+
+/// the version of the opened document, used to reject responses computed for an older version
+pub fn document_version(backend: &Backend, uri: &Url) -> Option<i32> {
+    backend.opened_files.get(uri).map(|document| document.0)
+}
+
+/// remove everything known about the opened document
+fn forget_document(backend: &Backend, uri: &Url) {
+    backend.opened_files.remove(uri);
+    backend.edits.remove(uri);
+    backend.syntax.remove(uri);
+    backend.format_errors.remove(uri);
 }
@@ -0,0 +1,21 @@
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::*;
+
+use crate::lsp::error::FILE_NOT_OPEN;
+use crate::Backend;
+
+/// - folding ranges come from a syntax-only parse, so they follow the document as it is edited
+/// - the ranges fold whole lines, so there are no columns to convert to the negotiated encoding
+pub fn handle_folding_range(
+    backend: &Backend,
+    FoldingRangeParams {
+        text_document: TextDocumentIdentifier { uri },
+        ..
+    }: FoldingRangeParams,
+) -> Result<Option<Vec<FoldingRange>>> {
+    let Some(syntax) = backend.syntax.get(&uri) else {
+        return Err(FILE_NOT_OPEN);
+    };
+
+    Ok(Some(syntax.folding_ranges.clone()))
+}
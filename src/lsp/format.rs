@@ -1,151 +1,366 @@
-use std::process::Stdio;
-use std::str::Lines;
+use std::process::{ExitStatus, Stdio};
 
-use itertools::Itertools;
-use similar::{DiffOp, TextDiff};
+use ropey::Rope;
 use tokio::io::AsyncWriteExt as _;
 use tokio::process::Command;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 
-use crate::lsp::error::{self, FILE_NOT_OPEN};
+use crate::encoding::PositionEncoding;
+use crate::format::{self, FormatConfig};
+use crate::lsp::error::{self, CONTENT_MODIFIED, FILE_NOT_OPEN};
+use crate::lsp::{diagnostic, file_sync};
 use crate::Backend;
 
-#[expect(clippy::too_many_lines)]
 pub async fn handle_formatting(
     backend: &Backend,
     params: DocumentFormattingParams,
 ) -> Result<Option<Vec<TextEdit>>> {
-    let Some(document) = backend.opened_files.get(&params.text_document.uri) else {
+    let Some((version, original)) = document_text(backend, &params.text_document.uri) else {
         return Err(FILE_NOT_OPEN);
     };
+    let config = format_config(&params.text_document.uri);
+    let result = rustfmt(&original, &config, None).await;
+    let new_text = report(backend, &params.text_document.uri, result).await?;
+    ensure_version(backend, &params.text_document.uri, version)?;
+
+    Ok(Some(format::diff_edits(
+        &original,
+        &new_text,
+        backend.encoding(),
+    )))
+}
+
+/// only the selected lines are formatted, using rustfmt's `--file-lines` when it is supported
+pub async fn handle_range_formatting(
+    backend: &Backend,
+    DocumentRangeFormattingParams {
+        text_document: TextDocumentIdentifier { uri },
+        range,
+        ..
+    }: DocumentRangeFormattingParams,
+) -> Result<Option<Vec<TextEdit>>> {
+    let Some((version, original)) = document_text(backend, &uri) else {
+        return Err(FILE_NOT_OPEN);
+    };
+    // a selection ending at the start of a line does not include that line
+    let end = if range.end.character == 0 && range.end.line > range.start.line {
+        range.end.line - 1
+    } else {
+        range.end.line
+    };
+
+    let config = format_config(&uri);
+    let result = format_lines(
+        &original,
+        &config,
+        backend.encoding(),
+        range.start.line,
+        end,
+    )
+    .await;
+
+    let edits = report(backend, &uri, result).await?;
+    ensure_version(backend, &uri, version)?;
+
+    Ok(Some(edits))
+}
+
+/// - `}` formats the block that it closes
+/// - `;` formats the statement on the current line
+/// - a newline indents the new line to match the surrounding code
+///
+/// formatting while typing is best effort, since the document is often incomplete, so a failure
+/// results in no edits instead of an error
+pub async fn handle_on_type_formatting(
+    backend: &Backend,
+    DocumentOnTypeFormattingParams {
+        text_document_position:
+            TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+        ch,
+        options,
+    }: DocumentOnTypeFormattingParams,
+) -> Result<Option<Vec<TextEdit>>> {
+    let Some((version, document)) = backend.opened_files.get(&uri).map(|x| x.clone()) else {
+        return Err(FILE_NOT_OPEN);
+    };
+    let encoding = backend.encoding();
+    let position = encoding.decode_position(&document, position);
+    let line = position.line as usize;
+    if line >= document.len_lines() {
+        return Ok(None);
+    }
+
+    let start = match ch.as_str() {
+        "}" => {
+            let close = document.line_to_char(line) + position.character as usize;
+            match open_brace_line(&document, close.saturating_sub(1)) {
+                Some(start) => start,
+                None => return Ok(None),
+            }
+        }
+        ";" => line,
+        "\n" => {
+            let edit = indent_line(&document, encoding, line, &options);
+            return Ok(edit.map(|edit| Vec::from([edit])));
+        }
+        _ => return Ok(None),
+    };
+
+    #[expect(clippy::cast_possible_truncation)]
+    let edits = format_lines(
+        &document.to_string(),
+        &format_config(&uri),
+        encoding,
+        start as u32,
+        position.line,
+    )
+    .await
+    .ok();
+    // edits for an older version would be applied at the wrong positions
+    if ensure_version(backend, &uri, version).is_err() {
+        return Ok(None);
+    }
+    Ok(edits)
+}
+
+/// the version and text of an opened document, copied so that the map is not locked while rustfmt
+/// runs
+fn document_text(backend: &Backend, uri: &Url) -> Option<(i32, String)> {
+    backend
+        .opened_files
+        .get(uri)
+        .map(|x| (x.0, x.1.to_string()))
+}
+
+/// fail if the document was edited since the version that the edits were computed for
+fn ensure_version(backend: &Backend, uri: &Url, version: i32) -> Result<()> {
+    if file_sync::document_version(backend, uri) == Some(version) {
+        Ok(())
+    } else {
+        Err(CONTENT_MODIFIED)
+    }
+}
+
+/// the rustfmt configuration of the package that owns the document
+fn format_config(uri: &Url) -> FormatConfig {
+    uri.to_file_path().map_or_else(
+        |()| FormatConfig::default(),
+        |path| FormatConfig::discover(&path),
+    )
+}
+
+/// format the lines from `start` to `end` (inclusive, zero-based) and keep only the edits within
+/// them, since rustfmt may still touch the surrounding lines of a partially selected item
+/// - a stable rustfmt rejects `--file-lines`, in which case the whole file is formatted and the
+///   edits are filtered the same way
+async fn format_lines(
+    original: &str,
+    config: &FormatConfig,
+    encoding: PositionEncoding,
+    start: u32,
+    end: u32,
+) -> std::result::Result<Vec<TextEdit>, Failure> {
+    let new_text = match rustfmt(original, config, Some((start + 1, end + 1))).await {
+        Err(failure) if failure.rejected_file_lines() => rustfmt(original, config, None).await?,
+        result => result?,
+    };
+
+    // edits of whole lines end at the start of the line after them
+    let end = Position::new(end + 1, 0);
+    Ok(format::diff_edits(original, &new_text, encoding)
+        .into_iter()
+        .filter(|edit| edit.range.start.line >= start && edit.range.end <= end)
+        .collect())
+}
+
+/// a failed run of rustfmt, without a status if rustfmt could not be run at all
+struct Failure {
+    status: Option<ExitStatus>,
+    stderr: String,
+}
+
+impl From<std::io::Error> for Failure {
+    fn from(err: std::io::Error) -> Self {
+        Self {
+            status: None,
+            stderr: format!("failed to run rustfmt: {err}"),
+        }
+    }
+}
+
+impl Failure {
+    /// whether rustfmt failed because it does not accept the unstable options of range formatting,
+    /// such as a stable rustfmt with `Unrecognized option: 'unstable-features'`
+    fn rejected_file_lines(&self) -> bool {
+        self.stderr.contains("unstable-features") || self.stderr.contains("file-lines")
+    }
+}
+
+/// publish the errors of a failed run as diagnostics of the document, or clear the errors of a
+/// previous run if it succeeded
+async fn report<T>(
+    backend: &Backend,
+    uri: &Url,
+    result: std::result::Result<T, Failure>,
+) -> Result<T> {
+    match result {
+        Ok(x) => {
+            if backend.format_errors.remove(uri).is_some() {
+                diagnostic::publish_document_diagnostics(backend, uri.clone()).await;
+            }
+            Ok(x)
+        }
+        Err(Failure { status, stderr }) => {
+            let errors = format::parse_errors(&stderr);
+            // errors without a location, such as an invalid configuration, are explained in the
+            // response instead
+            let reason = errors.first().map_or_else(
+                || {
+                    stderr
+                        .lines()
+                        .find(|line| !line.trim().is_empty())
+                        .map(str::to_owned)
+                },
+                |error| {
+                    Some(format!(
+                        "{} (line {})",
+                        error.message,
+                        error.range.start.line + 1
+                    ))
+                },
+            );
+            backend.format_errors.insert(uri.clone(), errors);
+            diagnostic::publish_document_diagnostics(backend, uri.clone()).await;
+
+            Err(error::rustfmt_failed(status, reason.as_deref()))
+        }
+    }
+}
+
+/// run rustfmt on the text with the configuration of its package, optionally restricted to a range
+/// of one-based lines
+/// `--file-lines` is an unstable option that only a nightly rustfmt binary accepts
+async fn rustfmt(
+    original: &str,
+    config: &FormatConfig,
+    lines: Option<(u32, u32)>,
+) -> std::result::Result<String, Failure> {
+    let mut command = Command::new("rustfmt");
+    command.args(config.args());
+    if let Some((start, end)) = lines {
+        command.args([
+            "--unstable-features".to_owned(),
+            "--file-lines".to_owned(),
+            format!(r#"[{{"file":"stdin","range":[{start},{end}]}}]"#),
+        ]);
+    }
 
     // spawn rustfmt
-    let mut child = Command::new("rustfmt")
-        .args(["--edition", "2021"])
+    let mut child = command
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()
-        .expect("failed to spawn rustfmt");
+        .stderr(Stdio::piped())
+        .spawn()?;
 
     // write the contents to rustfmt's stdin
-    let mut stdin = child.stdin.take().expect("failed to open stdin");
+    let mut stdin = child.stdin.take().expect("stdin is piped");
     let write_stdin = async {
-        let original = document.to_string();
-        stdin
-            .write_all(original.as_bytes())
-            .await
-            .expect("failed to write to stdin");
+        // the write only fails if rustfmt exited early, which its status and stderr explain
+        let _ = stdin.write_all(original.as_bytes()).await;
         drop(stdin); // ensure rustfmt receives EOF
-        original
     };
 
     // wait for rustfmt to finish
-    let (original, output) = tokio::join!(write_stdin, child.wait_with_output());
-    let output = output.expect("failed to wait on rustfmt");
+    let ((), output) = tokio::join!(write_stdin, child.wait_with_output());
+    let output = output?;
 
     if !output.status.success() {
-        return Err(error::rustfmt_failed(output.status));
+        return Err(Failure {
+            status: Some(output.status),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
     }
 
-    // diff the original and formatted text
-    let new_text = String::from_utf8(output.stdout).expect("rustfmt output was not valid utf-8");
-    let mut edits = Vec::new();
-    let diff = TextDiff::from_lines(&original, &new_text);
-    let mut lines = new_text.lines();
-
-    // convert the diff to text edits
-    for op in diff.ops() {
-        match op {
-            DiffOp::Equal { len, .. } => {
-                let (_, xs) = lines.split_at(*len);
-                lines = xs;
-            }
-            DiffOp::Delete {
-                old_index, old_len, ..
-            } => {
-                edits.push(TextEdit {
-                    range: Range {
-                        #[expect(clippy::cast_possible_truncation)]
-                        start: Position {
-                            line: *old_index as _,
-                            character: 0,
-                        },
-                        #[expect(clippy::cast_possible_truncation)]
-                        end: Position {
-                            line: (old_index + old_len) as _,
-                            character: 0,
-                        },
-                    },
-                    new_text: String::new(),
-                });
-            }
-            DiffOp::Insert {
-                old_index, new_len, ..
-            } => {
-                let (new_text, xs) = lines.split_at(*new_len);
-                lines = xs;
-
-                edits.push(TextEdit {
-                    range: Range {
-                        #[expect(clippy::cast_possible_truncation)]
-                        start: Position {
-                            line: *old_index as _,
-                            character: 0,
-                        },
-                        #[expect(clippy::cast_possible_truncation)]
-                        end: Position {
-                            line: *(old_index) as _,
-                            character: 0,
-                        },
-                    },
-                    new_text: new_text.into_iter().collect(),
-                });
-            }
-            DiffOp::Replace {
-                old_index,
-                old_len,
-                new_len,
-                ..
-            } => {
-                let (new_text, xs) = lines.split_at(*new_len);
-                lines = xs;
-
-                edits.push(TextEdit {
-                    range: Range {
-                        #[expect(clippy::cast_possible_truncation)]
-                        start: Position {
-                            line: *old_index as _,
-                            character: 0,
-                        },
-                        #[expect(clippy::cast_possible_truncation)]
-                        end: Position {
-                            line: (old_index + old_len) as _,
-                            character: 0,
-                        },
-                    },
-                    new_text: new_text.collect(),
-                });
-            }
-        }
+    Ok(String::from_utf8(output.stdout).expect("rustfmt output was not valid utf-8"))
+}
+
+/// the line containing the `{` that matches the `}` at the given char index
+/// braces inside strings and comments are not skipped, which is good enough while typing
+fn open_brace_line(document: &Rope, close: usize) -> Option<usize> {
+    if document.get_char(close)? != '}' {
+        return None;
     }
 
-    debug_assert_eq!(lines.next(), None);
-    Ok(Some(edits))
+    let mut depth = 0usize;
+    for idx in (0..close).rev() {
+        match document.char(idx) {
+            '}' => depth += 1,
+            '{' if depth == 0 => return Some(document.char_to_line(idx)),
+            '{' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
 }
 
-trait Splitable<'a> {
-    fn split_at(self, n: usize) -> (impl Iterator<Item = &'a str>, Self);
-}
+/// indent a new line one level deeper than the previous non-blank line if that line opens a
+/// delimiter, and one level shallower if the new line starts by closing one
+fn indent_line(
+    document: &Rope,
+    encoding: PositionEncoding,
+    line: usize,
+    options: &FormattingOptions,
+) -> Option<TextEdit> {
+    let unit = if options.insert_spaces {
+        " ".repeat(options.tab_size as usize)
+    } else {
+        "\t".to_owned()
+    };
+    let text = document.line(line).to_string();
+    let text = text.trim_end_matches(['\n', '\r']);
+    let content = text.trim_start();
 
-impl<'a> Splitable<'a> for Lines<'a> {
-    fn split_at(mut self, n: usize) -> (impl Iterator<Item = &'a str>, Self) {
-        let x = (0..n)
-            .filter_map(|_| self.next())
-            .collect::<Vec<_>>()
-            .into_iter()
-            .interleave_shortest(std::iter::repeat("\n"));
-        (x, self)
+    let previous = (0..line)
+        .rev()
+        .map(|idx| document.line(idx).to_string())
+        .find(|x| !x.trim().is_empty())
+        .unwrap_or_default();
+    let previous = previous.trim_end();
+    let mut indent = previous.len() - previous.trim_start().len();
+    let mut levels = 0;
+    if previous.ends_with(['{', '(', '[']) {
+        levels += 1;
     }
+    if content.starts_with(['}', ')', ']']) {
+        if levels > 0 {
+            levels -= 1;
+        } else {
+            indent = indent.saturating_sub(unit.len());
+        }
+    }
+    let new_indent = format!("{}{}", &previous[..indent], unit.repeat(levels));
+
+    let old_indent = &text[..text.len() - content.len()];
+    if old_indent == new_indent {
+        return None;
+    }
+
+    #[expect(clippy::cast_possible_truncation)]
+    Some(TextEdit {
+        range: Range {
+            start: Position {
+                line: line as u32,
+                character: 0,
+            },
+            end: Position {
+                line: line as u32,
+                character: encoding.units(old_indent),
+            },
+        },
+        new_text: new_indent,
+    })
 }
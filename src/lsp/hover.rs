@@ -1,5 +1,6 @@
 use tower_lsp::lsp_types::*;
 
+use crate::encoding::Converter;
 use crate::Backend;
 
 pub fn handle_hover(
@@ -13,10 +14,12 @@ pub fn handle_hover(
         ..
     }: HoverParams,
 ) -> std::option::Option<tower_lsp::lsp_types::Hover> {
+    let mut converter = Converter::new(backend);
+    let position = converter.decode(&uri, position);
     let symbol = backend.symbols.lock().ok()?.query(&uri, position)?;
 
     Some(Hover {
-        range: Some(symbol.range),
+        range: Some(converter.encode_range(&uri, symbol.range)),
         contents: HoverContents::Scalar(MarkedString::from_language_code(
             "rust".to_owned(),
             format!("{}: {}", symbol.name, symbol.ty),
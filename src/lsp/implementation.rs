@@ -0,0 +1,34 @@
+use tower_lsp::lsp_types::request::{GotoImplementationParams, GotoImplementationResponse};
+use tower_lsp::lsp_types::*;
+
+use crate::encoding::Converter;
+use crate::Backend;
+
+pub fn handle_implementation(
+    backend: &Backend,
+    GotoImplementationParams {
+        text_document_position_params:
+            TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+        ..
+    }: GotoImplementationParams,
+) -> Option<GotoImplementationResponse> {
+    let mut converter = Converter::new(backend);
+    let position = converter.decode(&uri, position);
+
+    // the implementations are only known for the document at the time of the last check
+    let position = backend.position_to_checked(&uri, position)?;
+
+    let implementations = backend.implementations.lock().expect("poisoned");
+    let locations = implementations.implementations(&uri, position)?;
+    // implementations in text that was edited since then are dropped
+    Some(GotoImplementationResponse::Array(
+        locations
+            .iter()
+            .filter_map(|location| backend.location_from_checked(location.clone()))
+            .map(|location| converter.encode_location(location))
+            .collect(),
+    ))
+}
@@ -0,0 +1,58 @@
+use ropey::Rope;
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::*;
+
+use crate::encoding::PositionEncoding;
+use crate::lsp::error::FILE_NOT_OPEN;
+use crate::Backend;
+
+/// selection ranges come from a syntax-only parse, so they follow the document as it is edited
+pub fn handle_selection_range(
+    backend: &Backend,
+    SelectionRangeParams {
+        text_document: TextDocumentIdentifier { uri },
+        positions,
+        ..
+    }: SelectionRangeParams,
+) -> Result<Option<Vec<SelectionRange>>> {
+    let Some(syntax) = backend.syntax.get(&uri) else {
+        return Err(FILE_NOT_OPEN);
+    };
+    // the whole file is the outermost range, so it is only missing if the parse failed
+    if syntax.selection_ranges.is_empty() {
+        return Ok(None);
+    }
+
+    let encoding = backend.encoding();
+    let Some(document) = backend.opened_files.get(&uri) else {
+        return Err(FILE_NOT_OPEN);
+    };
+    let (_, document) = &*document;
+
+    Ok(Some(
+        positions
+            .into_iter()
+            .map(|position| {
+                let position = encoding.decode_position(document, position);
+                encode_selection_range(document, encoding, syntax.selection_range(position))
+            })
+            .collect(),
+    ))
+}
+
+/// convert the ranges of the selection range and its parents to the negotiated encoding
+fn encode_selection_range(
+    document: &Rope,
+    encoding: PositionEncoding,
+    selection_range: SelectionRange,
+) -> SelectionRange {
+    SelectionRange {
+        range: Range {
+            start: encoding.encode_position(document, selection_range.range.start),
+            end: encoding.encode_position(document, selection_range.range.end),
+        },
+        parent: selection_range
+            .parent
+            .map(|parent| Box::new(encode_selection_range(document, encoding, *parent))),
+    }
+}
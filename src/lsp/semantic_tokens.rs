@@ -0,0 +1,76 @@
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::*;
+
+use crate::lsp::error::FILE_NOT_OPEN;
+use crate::Backend;
+
+/// inactive code is marked as a comment so that it is dimmed by themes that do not know the
+/// `inactive` modifier
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: Vec::from([SemanticTokenType::COMMENT]),
+        token_modifiers: Vec::from([SemanticTokenModifier::new("inactive")]),
+    }
+}
+
+/// only the code disabled by `#[cfg(...)]` in every check is highlighted, with a token per line
+/// since multiline tokens are not supported by every client
+pub fn handle_semantic_tokens_full(
+    backend: &Backend,
+    SemanticTokensParams {
+        text_document: TextDocumentIdentifier { uri },
+        ..
+    }: SemanticTokensParams,
+) -> Result<Option<SemanticTokensResult>> {
+    let (Some(syntax), Some(document)) = (backend.syntax.get(&uri), backend.opened_files.get(&uri))
+    else {
+        return Err(FILE_NOT_OPEN);
+    };
+    let (_, document) = &*document;
+    let encoding = backend.encoding();
+    let cfgs = backend.cfgs.lock().expect("poisoned");
+
+    let mut data = Vec::new();
+    let mut previous = Position::new(0, 0);
+    for region in cfgs.inactive_regions(&uri, &syntax.cfg_regions) {
+        let Range { start, end } = region.range;
+        for line in start.line..=end.line {
+            // the document may have changed since it was parsed
+            if line as usize >= document.len_lines() {
+                break;
+            }
+            let text = document.line(line as usize);
+            let from = if line == start.line {
+                encoding.encode_column(text, start.character)
+            } else {
+                0
+            };
+            let to = if line == end.line {
+                encoding.encode_column(text, end.character)
+            } else {
+                encoding.encode_column(text, u32::MAX)
+            };
+            if to <= from {
+                continue;
+            }
+
+            data.push(SemanticToken {
+                delta_line: line - previous.line,
+                delta_start: if line == previous.line {
+                    from - previous.character
+                } else {
+                    from
+                },
+                length: to - from,
+                token_type: 0,
+                token_modifiers_bitset: 1,
+            });
+            previous = Position::new(line, from);
+        }
+    }
+
+    Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+        result_id: None,
+        data,
+    })))
+}
@@ -0,0 +1,312 @@
+use ropey::Rope;
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::*;
+
+use crate::encoding::PositionEncoding;
+use crate::lsp::error::FILE_NOT_OPEN;
+use crate::signature::Signature;
+use crate::Backend;
+
+/// maximum number of characters searched backwards for the opening parenthesis of a call
+const MAX_CALL_LENGTH: usize = 4096;
+
+/// maximum number of signatures shown when the callee is only known by its name
+const MAX_SIGNATURES: usize = 8;
+
+#[allow(clippy::module_name_repetitions)]
+pub fn handle_signature_help(
+    backend: &Backend,
+    SignatureHelpParams {
+        text_document_position_params:
+            TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+        ..
+    }: SignatureHelpParams,
+) -> Result<Option<SignatureHelp>> {
+    let Some(document) = backend.opened_files.get(&uri) else {
+        return Err(FILE_NOT_OPEN);
+    };
+    let (_, document) = &*document;
+    let position = backend.encoding().decode_position(document, position);
+    let cursor = (document.line_to_char(position.line as _) + position.character as usize)
+        .min(document.len_chars());
+    let Some(CallContext {
+        name,
+        name_end,
+        is_method,
+        argument,
+    }) = call_context(document, cursor)
+    else {
+        return Ok(None);
+    };
+
+    // the callee ends right before the `(`, which is mapped to the document of the last check
+    let name_end = match backend.edits.get(&uri) {
+        Some(edits) => edits.map_to_checked(name_end),
+        None => Some(name_end),
+    };
+
+    // fall back to all functions with the same name if the call was not part of the last check
+    let signatures = backend.signatures.lock().expect("poisoned");
+    let candidates = if let Some(callee) = name_end.and_then(|end| signatures.callee(&uri, end)) {
+        Vec::from([callee])
+    } else {
+        let mut candidates = signatures
+            .by_name(&name)
+            .filter(|(_, signature)| !is_method || signature.has_self)
+            .collect::<Vec<_>>();
+        candidates.sort_unstable_by_key(|(path, _)| *path);
+        candidates.truncate(MAX_SIGNATURES);
+        candidates
+    };
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+
+    let signatures = candidates
+        .into_iter()
+        .map(|(_, signature)| {
+            signature_information(signature, backend.encoding(), is_method, argument)
+        })
+        .collect();
+    Ok(Some(SignatureHelp {
+        signatures,
+        active_signature: Some(0),
+        active_parameter: None,
+    }))
+}
+
+fn signature_information(
+    Signature {
+        label,
+        parameters,
+        documentation,
+        has_self,
+    }: &Signature,
+    encoding: PositionEncoding,
+    is_method: bool,
+    argument: u32,
+) -> SignatureInformation {
+    // the receiver of a method call is the `self` parameter
+    let active_parameter = if is_method && *has_self {
+        argument + 1
+    } else {
+        argument
+    };
+
+    // the offsets are counted in chars, like positions in the document
+    let offset =
+        |chars: u32| encoding.units(&label.chars().take(chars as usize).collect::<String>());
+
+    SignatureInformation {
+        label: label.clone(),
+        documentation: documentation.as_ref().map(|documentation| {
+            Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: documentation.clone(),
+            })
+        }),
+        parameters: Some(
+            parameters
+                .iter()
+                .map(|[start, end]| ParameterInformation {
+                    label: ParameterLabel::LabelOffsets([offset(*start), offset(*end)]),
+                    documentation: None,
+                })
+                .collect(),
+        ),
+        active_parameter: Some(active_parameter),
+    }
+}
+
+/// the call surrounding the cursor
+struct CallContext {
+    /// name of the called function
+    name: String,
+    /// end of the name of the called function
+    name_end: Position,
+    /// whether the method call syntax is used
+    is_method: bool,
+    /// index of the argument the cursor is in
+    argument: u32,
+}
+
+/// find the innermost unclosed `(` before the cursor and the name of the function before it
+fn call_context(document: &Rope, cursor: usize) -> Option<CallContext> {
+    let start = cursor.saturating_sub(MAX_CALL_LENGTH);
+    let mut chars = document.chars_at(cursor).reversed();
+    let mut idx = cursor;
+    let mut depth = 0_u32;
+    let mut argument = 0;
+
+    // count the commas at the same depth as the cursor until the opening parenthesis
+    loop {
+        if idx == start {
+            return None;
+        }
+        let c = chars.next()?;
+        idx -= 1;
+        match c {
+            ')' | ']' | '}' => depth += 1,
+            '(' if depth == 0 => break,
+            '[' | '{' | ';' if depth == 0 => return None,
+            '(' | '[' | '{' => depth -= 1,
+            ',' if depth == 0 => argument += 1,
+            _ => {}
+        }
+    }
+
+    // skip whitespace and a turbofish between the name and the parenthesis
+    let mut c = chars.next()?;
+    while c.is_whitespace() {
+        c = chars.next()?;
+        idx -= 1;
+    }
+    if c == '>' {
+        let mut depth = 1;
+        while depth > 0 {
+            match chars.next()? {
+                '>' => depth += 1,
+                '<' => depth -= 1,
+                _ => {}
+            }
+            idx -= 1;
+        }
+        if (chars.next()?, chars.next()?) != (':', ':') {
+            return None;
+        }
+        idx -= 3;
+        c = chars.next()?;
+    }
+
+    // the name of the function
+    let name_end = idx;
+    let mut name = String::new();
+    while c.is_alphanumeric() || c == '_' {
+        name.insert(0, c);
+        idx -= 1;
+        c = match chars.next() {
+            Some(c) => c,
+            None => break,
+        };
+    }
+    if name.is_empty() || name.starts_with(char::is_numeric) {
+        return None;
+    }
+    while c.is_whitespace() {
+        c = chars.next().unwrap_or_default();
+    }
+
+    let line = document.char_to_line(name_end);
+    #[expect(clippy::cast_possible_truncation)]
+    Some(CallContext {
+        name,
+        name_end: Position {
+            line: line as u32,
+            character: (name_end - document.line_to_char(line)) as u32,
+        },
+        is_method: c == '.',
+        argument,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the call context at the `$` in the text
+    fn context(text: &str) -> Option<CallContext> {
+        let cursor = text.chars().position(|c| c == '$').expect("cursor");
+        call_context(&Rope::from(text.replacen('$', "", 1)), cursor)
+    }
+
+    #[test]
+    fn arguments() {
+        let call = context("let p = Point::new(1, $").expect("call");
+        assert_eq!(call.name, "new");
+        assert_eq!(call.name_end, Position::new(0, 18));
+        assert!(!call.is_method);
+        assert_eq!(call.argument, 1);
+
+        // commas of nested calls and delimiters are not counted
+        let call = context("foo(bar(1, 2), [3, 4], $)").expect("call");
+        assert_eq!(call.name, "foo");
+        assert_eq!(call.argument, 2);
+
+        let call = context("foo(bar(1, $").expect("call");
+        assert_eq!(call.name, "bar");
+        assert_eq!(call.argument, 1);
+    }
+
+    #[test]
+    fn methods_and_turbofish() {
+        let call = context("let n = p\n    .area ($)").expect("call");
+        assert_eq!(call.name, "area");
+        assert_eq!(call.name_end, Position::new(1, 9));
+        assert!(call.is_method);
+        assert_eq!(call.argument, 0);
+
+        let call = context("iter.collect::<Vec<_>>($").expect("call");
+        assert_eq!(call.name, "collect");
+        assert_eq!(call.name_end, Position::new(0, 12));
+        assert!(call.is_method);
+    }
+
+    #[test]
+    fn non_ascii() {
+        let call = context("let é = größe(\"😀\", $").expect("call");
+        assert_eq!(call.name, "größe");
+        assert_eq!(call.name_end, Position::new(0, 13));
+        assert_eq!(call.argument, 1);
+    }
+
+    #[test]
+    fn label_offsets() {
+        let signature = Signature {
+            label: "fn f(é: u8, 😀: u8)".to_owned(),
+            parameters: Vec::from([[5, 10], [12, 17]]),
+            documentation: None,
+            has_self: false,
+        };
+        let offsets = |encoding| {
+            signature_information(&signature, encoding, false, 0)
+                .parameters
+                .expect("parameters")
+                .into_iter()
+                .map(|parameter| parameter.label)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            offsets(PositionEncoding::Utf8),
+            [
+                ParameterLabel::LabelOffsets([5, 11]),
+                ParameterLabel::LabelOffsets([13, 21])
+            ]
+        );
+        assert_eq!(
+            offsets(PositionEncoding::Utf16),
+            [
+                ParameterLabel::LabelOffsets([5, 10]),
+                ParameterLabel::LabelOffsets([12, 18])
+            ]
+        );
+        assert_eq!(
+            offsets(PositionEncoding::Utf32),
+            [
+                ParameterLabel::LabelOffsets([5, 10]),
+                ParameterLabel::LabelOffsets([12, 17])
+            ]
+        );
+    }
+
+    #[test]
+    fn not_a_call() {
+        assert!(context("let x = (1, $").is_none());
+        assert!(context("let x = [foo($]").is_some());
+        assert!(context("foo(1);\nlet x = $").is_none());
+        assert!(context("let v = vec![1, $").is_none());
+        assert!(context("let n = 1($").is_none());
+    }
+}
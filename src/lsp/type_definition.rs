@@ -0,0 +1,34 @@
+use tower_lsp::lsp_types::request::{GotoTypeDefinitionParams, GotoTypeDefinitionResponse};
+use tower_lsp::lsp_types::*;
+
+use crate::encoding::Converter;
+use crate::Backend;
+
+pub fn handle_type_definition(
+    backend: &Backend,
+    GotoTypeDefinitionParams {
+        text_document_position_params:
+            TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+        ..
+    }: GotoTypeDefinitionParams,
+) -> Option<GotoTypeDefinitionResponse> {
+    let mut converter = Converter::new(backend);
+    let position = converter.decode(&uri, position);
+
+    // bindings are only known for the document at the time of the last check
+    let position = backend.position_to_checked(&uri, position)?;
+
+    let symbol = backend
+        .symbols
+        .lock()
+        .expect("poisoned")
+        .query(&uri, position)?;
+    // a definition in text that was edited since then is dropped
+    let location = backend.location_from_checked(symbol.type_definition?)?;
+    Some(GotoTypeDefinitionResponse::Scalar(
+        converter.encode_location(location),
+    ))
+}
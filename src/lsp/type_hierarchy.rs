@@ -0,0 +1,153 @@
+use serde_json::Value;
+use tower_lsp::lsp_types::*;
+
+use crate::encoding::Converter;
+use crate::implementation::TypeRelations;
+use crate::item::{Item, ItemTable};
+use crate::Backend;
+
+/// the type hierarchy capability is missing from the server capabilities of `lsp-types`, so it is
+/// registered dynamically instead
+pub async fn register_type_hierarchy(backend: &Backend) {
+    let options = TypeHierarchyRegistrationOptions {
+        text_document_registration_options: TextDocumentRegistrationOptions {
+            document_selector: Some(Vec::from([DocumentFilter {
+                language: Some("rust".to_owned()),
+                scheme: Some("file".to_owned()),
+                pattern: None,
+            }])),
+        },
+        type_hierarchy_options: TypeHierarchyOptions {
+            work_done_progress_options: WorkDoneProgressOptions {
+                work_done_progress: Some(false),
+            },
+        },
+        static_registration_options: StaticRegistrationOptions { id: None },
+    };
+    let registration = Registration {
+        id: "textDocument/prepareTypeHierarchy".to_owned(),
+        method: "textDocument/prepareTypeHierarchy".to_owned(),
+        register_options: serde_json::to_value(options).ok(),
+    };
+
+    if let Err(err) = backend
+        .client
+        .register_capability(Vec::from([registration]))
+        .await
+    {
+        backend
+            .client
+            .log_message(
+                MessageType::WARNING,
+                format!("failed to register the type hierarchy: {err}"),
+            )
+            .await;
+    }
+}
+
+/// find the trait or type named at the given position
+pub fn handle_prepare_type_hierarchy(
+    backend: &Backend,
+    TypeHierarchyPrepareParams {
+        text_document_position_params:
+            TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+        ..
+    }: TypeHierarchyPrepareParams,
+) -> Option<Vec<TypeHierarchyItem>> {
+    let mut converter = Converter::new(backend);
+    let position = converter.decode(&uri, position);
+
+    // references are only known for the document at the time of the last check
+    let position = backend.position_to_checked(&uri, position)?;
+
+    let items = backend.items.lock().expect("poisoned");
+    let implementations = backend.implementations.lock().expect("poisoned");
+    let reference = implementations.reference(&uri, position)?;
+    let item = hierarchy_item(backend, &mut converter, &items, &reference.path)?;
+    Some(Vec::from([item]))
+}
+
+/// supertraits of a trait, or traits implemented by a type
+pub fn handle_supertypes(
+    backend: &Backend,
+    TypeHierarchySupertypesParams { item, .. }: TypeHierarchySupertypesParams,
+) -> Option<Vec<TypeHierarchyItem>> {
+    related_items(backend, item, |relations| &relations.supertypes)
+}
+
+/// traits extending a trait and types implementing it
+pub fn handle_subtypes(
+    backend: &Backend,
+    TypeHierarchySubtypesParams { item, .. }: TypeHierarchySubtypesParams,
+) -> Option<Vec<TypeHierarchyItem>> {
+    related_items(backend, item, |relations| &relations.subtypes)
+}
+
+fn related_items(
+    backend: &Backend,
+    item: TypeHierarchyItem,
+    select: impl FnOnce(&TypeRelations) -> &Vec<String>,
+) -> Option<Vec<TypeHierarchyItem>> {
+    let Some(Value::String(path)) = item.data else {
+        return None;
+    };
+
+    let mut converter = Converter::new(backend);
+    let items = backend.items.lock().expect("poisoned");
+    let implementations = backend.implementations.lock().expect("poisoned");
+    let Some(relations) = implementations.relations.get(&path) else {
+        return Some(Vec::new());
+    };
+
+    // definitions of crates that were not checked, such as the standard library, are not listed
+    Some(
+        select(relations)
+            .iter()
+            .filter_map(|path| hierarchy_item(backend, &mut converter, &items, path))
+            .collect(),
+    )
+}
+
+/// - the path of the trait or type is stored in the data of the item to look up its relations
+///   later
+/// - `None` if the definition was edited since the last check
+fn hierarchy_item(
+    backend: &Backend,
+    converter: &mut Converter<'_>,
+    items: &ItemTable,
+    path: &str,
+) -> Option<TypeHierarchyItem> {
+    let (
+        uri,
+        Item {
+            name,
+            kind,
+            detail,
+            range,
+            selection_range,
+            ..
+        },
+    ) = items.by_path(path)?;
+    if !matches!(
+        *kind,
+        SymbolKind::INTERFACE | SymbolKind::STRUCT | SymbolKind::ENUM
+    ) {
+        return None;
+    }
+
+    let range = backend.range_from_checked(uri, *range)?;
+    let selection_range = backend.range_from_checked(uri, *selection_range)?;
+    Some(TypeHierarchyItem {
+        name: name.clone(),
+        kind: *kind,
+        tags: None,
+        detail: detail.clone(),
+        uri: uri.clone(),
+        range: converter.encode_range(uri, range),
+        selection_range: converter.encode_range(uri, selection_range),
+        data: Some(Value::String(path.to_owned())),
+    })
+}
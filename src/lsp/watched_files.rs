@@ -0,0 +1,125 @@
+use std::path::Path;
+
+use tower_lsp::lsp_types::*;
+
+use crate::lsp::diagnostic;
+use crate::Backend;
+
+/// files that change the results of a check, relative to any directory of the workspace
+const WATCHED_FILES: [&str; 5] = [
+    "**/*.rs",
+    "**/Cargo.toml",
+    "**/Cargo.lock",
+    "**/rust-toolchain",
+    "**/rust-toolchain.toml",
+];
+
+/// names of the watched files that change how the workspace is built, rather than its sources
+const RELOADED_FILES: [&str; 4] = [
+    "Cargo.toml",
+    "Cargo.lock",
+    "rust-toolchain",
+    "rust-toolchain.toml",
+];
+
+/// watch the files on disk so that changes made outside of the editor, such as a `git checkout` or
+/// a code generator, are checked without waiting for the next save
+pub async fn register_file_watchers(backend: &Backend) {
+    let options = DidChangeWatchedFilesRegistrationOptions {
+        watchers: WATCHED_FILES
+            .into_iter()
+            .map(|pattern| FileSystemWatcher {
+                glob_pattern: GlobPattern::String(pattern.to_owned()),
+                kind: None,
+            })
+            .collect(),
+    };
+    let registration = Registration {
+        id: "workspace/didChangeWatchedFiles".to_owned(),
+        method: "workspace/didChangeWatchedFiles".to_owned(),
+        register_options: serde_json::to_value(options).ok(),
+    };
+
+    if let Err(err) = backend
+        .client
+        .register_capability(Vec::from([registration]))
+        .await
+    {
+        backend
+            .client
+            .log_message(
+                MessageType::WARNING,
+                format!("failed to register the file watchers: {err}"),
+            )
+            .await;
+    }
+}
+
+/// - changes to opened documents are ignored if the file matches the document, since saving it
+///   already triggers a check, otherwise the document no longer follows the edits since the last
+///   check, which are dropped
+/// - files in the target directory are ignored, since the check itself writes to it
+/// - the workspace is reloaded when a manifest, the lockfile or the toolchain changes, the results
+///   of created and deleted files are updated by a regular check
+pub async fn handle_did_change_watched_files(
+    backend: &Backend,
+    DidChangeWatchedFilesParams { changes }: DidChangeWatchedFilesParams,
+) {
+    let changes = changes
+        .into_iter()
+        .filter(|FileEvent { uri, typ }| {
+            let Ok(path) = uri.to_file_path() else {
+                return false;
+            };
+            !in_target_dir(&path)
+                && (*typ != FileChangeType::CHANGED || !saved(backend, uri, &path))
+        })
+        .collect::<Vec<_>>();
+    if changes.is_empty() {
+        return;
+    }
+    for FileEvent { uri, .. } in &changes {
+        backend.edits.remove(uri);
+    }
+
+    let reload = changes.iter().any(|FileEvent { uri, .. }| {
+        Path::new(uri.path())
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| RELOADED_FILES.contains(&name))
+    });
+    backend
+        .client
+        .log_message(
+            MessageType::INFO,
+            format!(
+                "{} watched files changed, {}",
+                changes.len(),
+                if reload {
+                    "reloading the workspace"
+                } else {
+                    "checking the workspace"
+                }
+            ),
+        )
+        .await;
+
+    diagnostic::handle_diagnostics(backend).await;
+    backend.check_workspace(reload).await;
+}
+
+/// whether the file is an opened document whose text matches the file on disk, which is the case
+/// after the document was saved
+fn saved(backend: &Backend, uri: &Url, path: &Path) -> bool {
+    let Some(document) = backend.opened_files.get(uri) else {
+        return false;
+    };
+    std::fs::read_to_string(path).is_ok_and(|text| document.1 == text)
+}
+
+/// whether the path is inside a cargo target directory, which cargo marks with a `CACHEDIR.TAG`
+fn in_target_dir(path: &Path) -> bool {
+    path.ancestors()
+        .skip(1)
+        .any(|dir| dir.ends_with("target") && dir.join("CACHEDIR.TAG").is_file())
+}
@@ -0,0 +1,51 @@
+use tower_lsp::lsp_types::*;
+
+use crate::encoding::Converter;
+use crate::Backend;
+
+/// maximum number of symbols returned for a single query
+const MAX_RESULTS: usize = 128;
+
+/// queries ending with this character also search the items of dependencies, which are only
+/// indexed when `dependencySymbols` is enabled
+const DEPENDENCIES_SUFFIX: char = '*';
+
+#[allow(clippy::module_name_repetitions)]
+pub fn handle_workspace_symbol(
+    backend: &Backend,
+    WorkspaceSymbolParams { query, .. }: &WorkspaceSymbolParams,
+) -> Vec<SymbolInformation> {
+    let (query, include_dependencies) = match query.strip_suffix(DEPENDENCIES_SUFFIX) {
+        Some(query) => (query, true),
+        None => (query.as_str(), false),
+    };
+
+    let mut converter = Converter::new(backend);
+    let items = backend.items.lock().expect("poisoned");
+    items
+        .search(query, include_dependencies)
+        .into_iter()
+        .take(MAX_RESULTS)
+        .map(|(uri, item)| {
+            // the container is the path of the item without its own name
+            let container_name = item
+                .path
+                .as_ref()
+                .and_then(|path| path.rsplit_once("::"))
+                .map(|(container, _)| container.to_owned());
+
+            #[expect(deprecated)]
+            SymbolInformation {
+                name: item.name.clone(),
+                kind: item.kind,
+                tags: None,
+                deprecated: None,
+                location: converter.encode_location(Location {
+                    uri,
+                    range: item.selection_range,
+                }),
+                container_name,
+            }
+        })
+        .collect()
+}
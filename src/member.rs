@@ -0,0 +1,98 @@
+//! code related to the member table used for code completion
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::*;
+
+#[derive(Debug, Default)]
+pub struct MemberTable {
+    /// fields and methods of types, keyed by the type without any references
+    pub types: HashMap<String, Vec<Member>>,
+    /// children of modules, types and traits, keyed by their full path
+    pub paths: HashMap<String, Vec<Member>>,
+    /// types of expressions in each file
+    /// invariant:
+    /// - the expressions in the same file are sorted by their end position
+    pub exprs: HashMap<Url, Vec<ExprType>>,
+    /// items that can be imported into the files of a crate, keyed by the name of the crate
+    pub importables: HashMap<String, Vec<Importable>>,
+}
+
+impl MemberTable {
+    pub fn merge_replace(&mut self, other: Self) {
+        self.types.extend(other.types);
+        self.paths.extend(other.paths);
+        self.importables.extend(other.importables);
+        for (url, exprs) in other.exprs {
+            self.exprs.entry(url).insert_entry(exprs);
+        }
+    }
+
+    /// drop the results of the files that `keep` rejects, such as files that were deleted
+    pub fn retain_files(&mut self, keep: impl Fn(&Url) -> bool) {
+        self.exprs.retain(|url, _| keep(url));
+    }
+
+    /// find the type of the outermost expression that ends at the given position
+    pub fn expr_type(&self, url: &Url, end: Position) -> Option<&str> {
+        let exprs = self.exprs.get(url)?;
+        let idx = exprs.partition_point(|expr| expr.range.end < end);
+
+        exprs[idx..]
+            .iter()
+            .take_while(|expr| expr.range.end == end)
+            .min_by_key(|expr| expr.range.start)
+            .map(|expr| expr.ty.as_str())
+    }
+
+    /// find the children of the module, type or trait with the given path
+    /// the path may be partial, so `collections` matches `std::collections`
+    pub fn path_children(&self, path: &str) -> Option<(&str, &[Member])> {
+        let suffix = format!("::{path}");
+        self.paths
+            .iter()
+            .filter(|(key, _)| *key == path || key.ends_with(&suffix))
+            // prefer the shortest path since it is the most likely to be the one in scope
+            .min_by_key(|(key, _)| key.len())
+            .map(|(key, members)| (key.as_str(), members.as_slice()))
+    }
+}
+
+/// a field, method, associated item or module child that can be completed
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Member {
+    pub name: String,
+    pub kind: CompletionItemKind,
+    /// signature of a function or the type of a field
+    pub detail: Option<String>,
+    /// documentation of the member in markdown
+    pub documentation: Option<String>,
+    /// whether the member is a method that can be called with the `.` syntax
+    pub has_self: bool,
+}
+
+/// an item that can be brought into scope with a `use` statement
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Importable {
+    pub name: String,
+    /// the path used in the `use` statement such as `std::collections::HashMap`
+    pub path: String,
+    pub kind: CompletionItemKind,
+}
+
+/// the type of an expression recorded by the embedded compiler
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct ExprType {
+    pub range: Range,
+    /// the type without any references
+    pub ty: String,
+}
+
+/// remove all leading references from the string representation of a type
+pub fn peel_refs(mut ty: &str) -> &str {
+    while let Some(rest) = ty.strip_prefix('&') {
+        ty = rest.trim_start_matches("mut ").trim_start();
+    }
+    ty
+}
@@ -0,0 +1,367 @@
+//! code related to the runnable table used for the code lenses that run tests, benches, binaries
+//! and doc tests
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Range, Url};
+
+use crate::config::CargoOptions;
+
+#[derive(Debug, Default)]
+pub struct RunnableTable {
+    /// invariant: the runnables in the same file are sorted by start position
+    pub inner: HashMap<Url, Vec<Runnable>>,
+}
+
+impl RunnableTable {
+    pub fn merge_replace(&mut self, other: Self) {
+        for (url, runnables) in other.inner {
+            self.inner.entry(url).insert_entry(runnables);
+        }
+    }
+
+    /// drop the results of the files that `keep` rejects, such as files that were deleted
+    pub fn retain_files(&mut self, keep: impl Fn(&Url) -> bool) {
+        self.inner.retain(|url, _| keep(url));
+    }
+
+    /// sorted paths of the other doc tests of the package whose paths contain the path of the doc
+    /// test, such as `Circle::area_mut` for `Circle::area`, which its filter also selects
+    /// paths with whitespace are left out since rustdoc splits its test arguments on whitespace
+    pub fn doctests_containing(&self, runnable: &Runnable) -> Vec<String> {
+        let mut paths = self
+            .inner
+            .values()
+            .flatten()
+            .filter(|other| {
+                other.kind == RunnableKind::Doctest
+                    && other.target.package == runnable.target.package
+                    && other.path != runnable.path
+                    && other.path.contains(&runnable.path)
+                    && !other.path.contains(char::is_whitespace)
+            })
+            .map(|other| other.path.clone())
+            .collect::<Vec<_>>();
+        paths.sort_unstable();
+        paths.dedup();
+        paths
+    }
+}
+
+/// an item that cargo can run, recorded by the embedded compiler
+/// it is also sent to the client as the argument of the commands of its code lenses
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Runnable {
+    pub kind: RunnableKind,
+    /// path of the item without the crate name, such as `tests::it_works`, which is how libtest
+    /// and rustdoc name the tests
+    pub path: String,
+    pub target: CargoTarget,
+    /// range of the item, including its attributes and doc comments
+    pub range: Range,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RunnableKind {
+    /// a function with `#[test]`
+    Test,
+    /// a function with `#[bench]`
+    Bench,
+    /// the `main` function of a binary, example or test without a harness
+    Main,
+    /// an item whose documentation has a code block that rustdoc runs as a test
+    Doctest,
+}
+
+/// the package and target that an item is compiled in, which only cargo knows
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CargoTarget {
+    pub package: String,
+    pub kind: TargetKind,
+    /// name of the target, such as the name of the file of an integration test
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TargetKind {
+    Lib,
+    Bin,
+    Test,
+    Bench,
+    Example,
+}
+
+impl Runnable {
+    /// arguments of the cargo command that runs the item, such as
+    /// `test -p minira --lib -- tests::it_works --exact`
+    /// - the options select the features and target like those of the check
+    /// - the skipped paths are the doc tests that the filter of a doc test also selects, see
+    ///   [`RunnableTable::doctests_containing`]
+    pub fn cargo_args(&self, options: &CargoOptions, skipped: &[String]) -> Vec<String> {
+        let mut args = self.build_args(options);
+        let test_args = self.test_args();
+        if !test_args.is_empty() {
+            args.push("--".to_owned());
+            args.extend(test_args);
+        }
+        if self.kind == RunnableKind::Doctest {
+            for path in skipped {
+                args.extend(["--skip".to_owned(), path.clone()]);
+            }
+        }
+        args
+    }
+
+    /// arguments of the cargo command before `--`, which select the package and the target
+    pub fn build_args(&self, options: &CargoOptions) -> Vec<String> {
+        let subcommand = match (self.kind, self.target.kind) {
+            (RunnableKind::Bench, _) | (RunnableKind::Main, TargetKind::Bench) => "bench",
+            (RunnableKind::Main, TargetKind::Bin | TargetKind::Example) => "run",
+            _ => "test",
+        };
+        let mut args = Vec::from([
+            subcommand.to_owned(),
+            "-p".to_owned(),
+            self.target.package.clone(),
+        ]);
+        if self.kind == RunnableKind::Doctest {
+            args.push("--doc".to_owned());
+        } else {
+            args.extend(self.target.target_args());
+        }
+        args.extend(options.cargo_args());
+        args
+    }
+
+    /// arguments of the test harness that select the item
+    /// - tests and benches are matched exactly so that tests whose names start with the same path
+    ///   are not run too
+    /// - doc tests are named like `src/lib.rs - Circle::area (line 12)`, but rustdoc splits its
+    ///   test arguments on whitespace, so only the path is matched and the other doc tests that
+    ///   contain it are skipped
+    pub fn test_args(&self) -> Vec<String> {
+        match self.kind {
+            RunnableKind::Test | RunnableKind::Bench => {
+                Vec::from([self.path.clone(), "--exact".to_owned()])
+            }
+            RunnableKind::Doctest => Vec::from([self.path.clone()]),
+            RunnableKind::Main => Vec::new(),
+        }
+    }
+}
+
+impl CargoTarget {
+    /// arguments of cargo commands that select the target
+    fn target_args(&self) -> Vec<String> {
+        let flag = match self.kind {
+            TargetKind::Lib => return Vec::from(["--lib".to_owned()]),
+            TargetKind::Bin => "--bin",
+            TargetKind::Test => "--test",
+            TargetKind::Bench => "--bench",
+            TargetKind::Example => "--example",
+        };
+        Vec::from([flag.to_owned(), self.name.clone()])
+    }
+}
+
+/// whether the documentation has a code block that rustdoc runs as a test
+/// blocks are rust code unless their info string names another language, and ignored blocks are
+/// skipped since they would not run
+pub fn has_doctest(doc: &str) -> bool {
+    let mut in_block = false;
+    for line in doc.lines() {
+        let line = line.trim_start();
+        let Some(info) = line
+            .strip_prefix("```")
+            .or_else(|| line.strip_prefix("~~~"))
+        else {
+            continue;
+        };
+        if in_block {
+            in_block = false;
+        } else if is_rust_block(info) {
+            return true;
+        } else {
+            in_block = true;
+        }
+    }
+    false
+}
+
+/// whether a code block with the info string, such as `rust,should_panic`, is a doc test
+fn is_rust_block(info: &str) -> bool {
+    let mut rust = true;
+    for token in info
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|token| !token.is_empty())
+    {
+        match token {
+            "rust" => return !info.contains("ignore"),
+            "should_panic" | "no_run" | "compile_fail" | "test_harness" | "standalone_crate" => {}
+            _ if token.starts_with("edition") => {}
+            _ => rust = false,
+        }
+    }
+    rust
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runnable(kind: RunnableKind, path: &str, target: TargetKind, name: &str) -> Runnable {
+        Runnable {
+            kind,
+            path: path.to_owned(),
+            target: CargoTarget {
+                package: "shapes".to_owned(),
+                kind: target,
+                name: name.to_owned(),
+            },
+            range: Range::default(),
+        }
+    }
+
+    #[test]
+    fn cargo_args() {
+        let options = CargoOptions::default();
+        assert_eq!(
+            runnable(RunnableKind::Test, "tests::area", TargetKind::Lib, "shapes")
+                .cargo_args(&options, &[]),
+            [
+                "test",
+                "-p",
+                "shapes",
+                "--lib",
+                "--",
+                "tests::area",
+                "--exact"
+            ]
+        );
+        assert_eq!(
+            runnable(RunnableKind::Bench, "perimeter", TargetKind::Bench, "perf")
+                .cargo_args(&options, &[]),
+            [
+                "bench",
+                "-p",
+                "shapes",
+                "--bench",
+                "perf",
+                "--",
+                "perimeter",
+                "--exact"
+            ]
+        );
+        assert_eq!(
+            runnable(RunnableKind::Main, "main", TargetKind::Bin, "draw").cargo_args(&options, &[]),
+            ["run", "-p", "shapes", "--bin", "draw"]
+        );
+        assert_eq!(
+            runnable(
+                RunnableKind::Doctest,
+                "Circle::area",
+                TargetKind::Lib,
+                "shapes"
+            )
+            .cargo_args(&options, &[]),
+            ["test", "-p", "shapes", "--doc", "--", "Circle::area"]
+        );
+        assert_eq!(
+            runnable(
+                RunnableKind::Doctest,
+                "Circle::area",
+                TargetKind::Lib,
+                "shapes"
+            )
+            .cargo_args(&options, &["Circle::area_mut".to_owned()]),
+            [
+                "test",
+                "-p",
+                "shapes",
+                "--doc",
+                "--",
+                "Circle::area",
+                "--skip",
+                "Circle::area_mut"
+            ]
+        );
+
+        let options = CargoOptions {
+            features: Vec::from(["gpu".to_owned()]),
+            ..CargoOptions::default()
+        };
+        assert_eq!(
+            runnable(RunnableKind::Test, "smoke", TargetKind::Test, "it").cargo_args(&options, &[]),
+            [
+                "test",
+                "-p",
+                "shapes",
+                "--test",
+                "it",
+                "--features",
+                "gpu",
+                "--",
+                "smoke",
+                "--exact"
+            ]
+        );
+    }
+
+    #[test]
+    fn doctests_containing() {
+        let url = Url::parse("file:///shapes/src/lib.rs").expect("valid url");
+        let area = runnable(RunnableKind::Doctest, "area", TargetKind::Lib, "shapes");
+        let mut other_package =
+            runnable(RunnableKind::Doctest, "area_sum", TargetKind::Lib, "shapes");
+        other_package.target.package = "plots".to_owned();
+        let table = RunnableTable {
+            inner: HashMap::from([(
+                url,
+                Vec::from([
+                    area.clone(),
+                    runnable(
+                        RunnableKind::Doctest,
+                        "Circle::area",
+                        TargetKind::Lib,
+                        "shapes",
+                    ),
+                    runnable(RunnableKind::Doctest, "area_mut", TargetKind::Lib, "shapes"),
+                    runnable(RunnableKind::Test, "tests::area", TargetKind::Lib, "shapes"),
+                    // split by rustdoc into `<Circle`, `as` and `Area>::area`
+                    runnable(
+                        RunnableKind::Doctest,
+                        "<Circle as Area>::area",
+                        TargetKind::Lib,
+                        "shapes",
+                    ),
+                    other_package,
+                ]),
+            )]),
+        };
+        assert_eq!(
+            table.doctests_containing(&area),
+            ["Circle::area", "area_mut"]
+        );
+    }
+
+    #[test]
+    fn doctests() {
+        assert!(has_doctest(
+            "adds one\n```\nassert_eq!(add_one(1), 2);\n```"
+        ));
+        assert!(has_doctest("```rust,should_panic\npanic!();\n```"));
+        assert!(has_doctest("```no_run\nloop {}\n```"));
+        assert!(!has_doctest("no examples"));
+        assert!(!has_doctest("```text\nnot rust\n```"));
+        assert!(!has_doctest("```ignore\nnot compiled\n```"));
+        assert!(!has_doctest("```rust,ignore\nnot compiled\n```"));
+        // the fence that closes a block of another language does not open a rust block
+        assert!(!has_doctest("```toml\n[dependencies]\n```\nmore text"));
+        assert!(has_doctest("```sh\ncargo run\n```\n```\nlet x = 1;\n```"));
+    }
+}
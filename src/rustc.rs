@@ -1,20 +1,36 @@
 //! code for interacting with the bundled nightly rustc compiler
 
+extern crate rustc_ast;
 extern crate rustc_driver;
 extern crate rustc_hir;
 extern crate rustc_interface;
 extern crate rustc_middle;
+extern crate rustc_session;
 extern crate rustc_span;
 
+mod implementation;
+mod member;
+mod signature;
+
+use rustc_ast::AttrStyle;
 use rustc_driver::{Callbacks, Compilation, RunCompiler};
+use rustc_hir::def::{DefKind, Res};
 use rustc_hir::intravisit::{self, Visitor};
-use rustc_hir::{HirId, Pat, PatKind};
+use rustc_hir::{
+    Body, BodyId, BodyOwnerKind, Expr, ExprKind, FieldDef, HirId, ImplItem, ImplItemKind,
+    Item as HirItem, ItemKind, MatchSource, Mutability, Pat, PatKind, Path as HirPath, QPath,
+    TraitItem, TraitItemKind, Variant,
+};
 use rustc_interface::interface::Compiler;
 use rustc_middle::hir::nested_filter::OnlyBodies;
-use rustc_middle::ty::{Ty, TyCtxt};
-use rustc_span::{FileName, RealFileName, SourceFile, Span};
+use rustc_middle::ty::adjustment::{Adjust, AutoBorrow, AutoBorrowMutability};
+use rustc_middle::ty::print::with_no_trimmed_paths;
+use rustc_middle::ty::{self, Ty, TyCtxt, TypeVisitableExt as _, TypeckResults};
+use rustc_session::filesearch;
+use rustc_span::def_id::{DefId, LocalDefId, CRATE_DEF_ID, LOCAL_CRATE};
+use rustc_span::{sym, BytePos, FileName, RealFileName, SourceFile, Span};
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::env;
 use std::ffi::OsString;
 use std::io::{BufRead as _, Write as _};
@@ -22,26 +38,171 @@ use std::path::Path;
 use std::sync::mpsc::{self, Sender};
 use std::sync::Arc;
 
-use cargo::core::compiler::{CompileMode, Executor};
+use cargo::core::compiler::{CompileKind, CompileMode, Executor, Unit};
 use cargo::core::manifest::Target;
 use cargo::core::package_id::PackageId;
-use cargo::core::{Package, Workspace};
-use cargo::ops::{self, CompileOptions};
+use cargo::core::resolver::CliFeatures;
+use cargo::core::{Package, TargetKind as CargoTargetKind, Workspace};
+use cargo::ops::{self, CompileFilter, CompileOptions};
 use cargo::util::errors::CargoResult;
 use cargo::util::GlobalContext;
 use cargo_util::ProcessBuilder;
+use serde::{Deserialize, Serialize};
 use tokio::task::JoinError;
-use tower_lsp::lsp_types::{Position, Range, Url};
+use tower_lsp::lsp_types::{DocumentHighlightKind, Location, Position, Range, SymbolKind, Url};
 
+use crate::cfg::{CfgOptions, CfgTable};
+use crate::config::{CargoOptions, Config};
+use crate::highlight::{Exit, ExitPoints, HighlightTable, Occurrence};
+use crate::implementation::{ImplementationTable, Reference, TypeRelations};
+use crate::item::{self, Item, ItemTable};
+use crate::member::{ExprType, Importable, Member, MemberTable};
+use crate::runnable::{self, CargoTarget, Runnable, RunnableKind, RunnableTable, TargetKind};
+use crate::signature::{Call, Signature, SignatureTable};
 use crate::symbol::{Symbol, SymbolTable};
 
+/// results of checking the workspace with the bundled nightly rustc compiler
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub symbols: SymbolTable,
+    pub items: ItemTable,
+    pub members: MemberTable,
+    pub signatures: SignatureTable,
+    pub implementations: ImplementationTable,
+    pub highlights: HighlightTable,
+    pub cfgs: CfgTable,
+    pub runnables: RunnableTable,
+    /// configurations that could not be checked and why
+    pub errors: Vec<String>,
+}
+
+impl Analysis {
+    fn add(&mut self, record: Record) {
+        match record {
+            Record::Symbol(url, symbol) => {
+                self.symbols.inner.entry(url).or_default().push(symbol);
+            }
+            Record::Item(url, item) => self.items.inner.entry(url).or_default().push(item),
+            Record::DependencyItem(url, item) => {
+                self.items.dependencies.insert(url.clone());
+                self.items.inner.entry(url).or_default().push(item);
+            }
+            Record::Expr(url, expr) => self.members.exprs.entry(url).or_default().push(expr),
+            Record::TypeMembers(ty, members) => {
+                extend_unique(self.members.types.entry(ty).or_default(), members);
+            }
+            Record::PathChildren(path, members) => {
+                extend_unique(self.members.paths.entry(path).or_default(), members);
+            }
+            Record::Importables(krate, importables) => {
+                extend_unique(
+                    self.members.importables.entry(krate).or_default(),
+                    importables,
+                );
+            }
+            Record::Signature(path, signature) => {
+                self.signatures.signatures.insert(path, signature);
+            }
+            Record::Call(url, call) => self.signatures.calls.entry(url).or_default().push(call),
+            Record::Reference(url, reference) => {
+                self.implementations
+                    .references
+                    .entry(url)
+                    .or_default()
+                    .push(reference);
+            }
+            Record::Implementations(path, locations) => {
+                extend_unique(
+                    self.implementations
+                        .implementations
+                        .entry(path)
+                        .or_default(),
+                    locations,
+                );
+            }
+            Record::TypeRelations(path, relations) => {
+                self.implementations.relations.insert(path, relations);
+            }
+            Record::Occurrence(url, occurrence) => {
+                self.highlights
+                    .occurrences
+                    .entry(url)
+                    .or_default()
+                    .push(occurrence);
+            }
+            Record::Cfgs(urls, options) => {
+                for url in urls {
+                    let compilations = self.cfgs.inner.entry(url).or_default();
+                    if !compilations.contains(&options) {
+                        compilations.push(options.clone());
+                    }
+                }
+            }
+            Record::Runnable(url, runnable) => {
+                self.runnables.inner.entry(url).or_default().push(runnable);
+            }
+            Record::ExitPoints(url, exit_points) => {
+                self.highlights
+                    .exit_points
+                    .entry(url)
+                    .or_default()
+                    .push(exit_points);
+            }
+        }
+    }
+
+    /// sort the records of each file by their position, which the tables rely on for lookups
+    fn sort(&mut self) {
+        // ranges must not overlap, so when configurations disagree on a symbol, such as the type of
+        // a binding on different targets, the stable sort keeps the one of the first configuration
+        for symbols in self.symbols.inner.values_mut() {
+            symbols.sort();
+            symbols.dedup_by(|a, b| a.range == b.range);
+        }
+        for items in self.items.inner.values_mut() {
+            item::sort_items(items);
+        }
+        for exprs in self.members.exprs.values_mut() {
+            exprs.sort_unstable_by_key(|expr| (expr.range.end, expr.range.start));
+            exprs.dedup();
+        }
+        for calls in self.signatures.calls.values_mut() {
+            calls.sort_unstable_by_key(|call| call.range.end);
+            calls.dedup();
+        }
+        for occurrences in self.highlights.occurrences.values_mut() {
+            occurrences.sort_unstable_by_key(|occurrence| occurrence.range.start);
+            occurrences.dedup();
+        }
+        for exit_points in self.highlights.exit_points.values_mut() {
+            exit_points.sort_unstable_by_key(|exit_points| exit_points.keyword.start);
+            exit_points.dedup();
+        }
+        for references in self.implementations.references.values_mut() {
+            references.sort_unstable_by_key(|reference| reference.range.start);
+            references.dedup();
+        }
+        // doc tests of libraries are recorded by both the normal and the test compilation
+        for runnables in self.runnables.inner.values_mut() {
+            runnables.sort_unstable_by_key(|runnable| runnable.range.start);
+            runnables.dedup();
+        }
+    }
+}
+
 /// run cargo check with the bundled nightly rustc compiler to get type information and diagnostics
-pub async fn check_workspace(manifest_path: &Path) -> Result<SymbolTable, JoinError> {
+/// - cargo only compiles the crates that changed since the last check, unless `reload` is set, in
+///   which case every crate checked by the embedded compiler is compiled again
+pub async fn check_workspace(
+    manifest_path: &Path,
+    config: Config,
+    reload: bool,
+) -> Result<Analysis, JoinError> {
     let path = manifest_path.to_owned();
-    tokio::task::spawn_blocking(move || check_workspace_aux(&path)).await
+    tokio::task::spawn_blocking(move || check_workspace_aux(&path, &config, reload)).await
 }
 
-fn check_workspace_aux(manifest_path: &Path) -> SymbolTable {
+fn check_workspace_aux(manifest_path: &Path, config: &Config, reload: bool) -> Analysis {
     // https://doc.rust-lang.org/nightly/nightly-rustc/cargo/ops/cargo_compile/index.html
     // set up cargo to perform checks
     // use a custom executor to hijack the rustc command to use the bundled nightly compiler
@@ -49,66 +210,212 @@ fn check_workspace_aux(manifest_path: &Path) -> SymbolTable {
     // TODO: send the rx to a scoped thread to process data as it comes rather than waiting for
     // cargo to finish
     let context = GlobalContext::default().expect("Failed to create a global context");
-    let workspace =
+    let mut workspace =
         Workspace::new(manifest_path, &context).expect("Failed to create Cargo workspace");
-    let compile_opts = CompileOptions::new(&context, CompileMode::Check { test: false })
-        .expect("Failed to create compile options");
+    // the check has its own target directory, since the crates that cargo check or clippy
+    // compiled for the diagnostics would otherwise be fresh and never go through the executor
+    workspace.set_target_dir(workspace.target_dir().join("minira"));
     let (tx, rx) = mpsc::channel();
     let custom_exec = Arc::new(CustomExecutor {
         members: workspace.members().map(Package::package_id).collect(),
+        extra_args: config.extra_args.clone(),
+        dependency_symbols: config.dependency_symbols,
+        reload,
         tx,
     }) as _;
 
-    ops::compile_with_exec(&workspace, &compile_opts, &custom_exec)
-        .expect("Failed to compile the project");
+    // every configuration sends its records to the same channel, so the results of files that are
+    // compiled in several configurations are merged below
+    let mut analysis = Analysis::default();
+    for configuration in config.all_configurations() {
+        if let Some(target) = configuration
+            .target
+            .as_deref()
+            .filter(|target| !target_installed(target))
+        {
+            analysis.errors.push(format!(
+                "skipped checking {}: the standard library for `{target}` is not installed, \
+                 install it with `rustup target add {target}`",
+                configuration.describe()
+            ));
+            continue;
+        }
 
-    // construct symbol table using data from cargo and rustc
-    let mut table = SymbolTable {
-        inner: HashMap::new(),
-    };
-    while let Ok((url, symbol)) = rx.try_recv() {
-        table.inner.entry(url).or_default().push(symbol);
+        let result = compile_options(&context, &configuration, config.all_targets).and_then(
+            |compile_opts| ops::compile_with_exec(&workspace, &compile_opts, &custom_exec),
+        );
+        if let Err(err) = result {
+            analysis.errors.push(format!(
+                "failed to check {}: {err:#}",
+                configuration.describe()
+            ));
+        }
     }
-    for symbols in table.inner.values_mut() {
-        symbols.sort_unstable();
+
+    // construct symbol and item tables using data from cargo and rustc
+    while let Ok(record) = rx.try_recv() {
+        analysis.add(record);
     }
+    analysis.sort();
 
-    table
+    analysis
 }
 
-type SymbolIpc = (Url, Symbol);
+/// options of a check with the features and target of the configuration
+/// with all targets, libraries are checked both normally and as tests like `cargo check
+/// --all-targets` does, so that code behind `#[cfg(test)]` and `#[cfg(not(test))]` is covered
+fn compile_options(
+    context: &GlobalContext,
+    configuration: &CargoOptions,
+    all_targets: bool,
+) -> CargoResult<CompileOptions> {
+    let mut compile_opts = CompileOptions::new(context, CompileMode::Check { test: false })?;
+    if all_targets {
+        compile_opts.filter = CompileFilter::new_all_targets();
+    }
+    compile_opts.cli_features = CliFeatures::from_command_line(
+        &configuration.features,
+        configuration.all_features,
+        !configuration.no_default_features,
+    )?;
+    if let Some(target) = &configuration.target {
+        compile_opts.build_config.requested_kinds =
+            CompileKind::from_requested_targets(context, std::slice::from_ref(target))?;
+    }
+    Ok(compile_opts)
+}
+
+/// whether the sysroot of the embedded compiler has the standard library of the target
+/// custom targets given as a path to a target specification are assumed to build their own
+fn target_installed(target: &str) -> bool {
+    if Path::new(target)
+        .extension()
+        .is_some_and(|ext| ext == "json")
+    {
+        return true;
+    }
+    filesearch::get_or_default_sysroot().is_ok_and(|sysroot| {
+        sysroot
+            .join("lib")
+            .join("rustlib")
+            .join(target)
+            .join("lib")
+            .is_dir()
+    })
+}
+
+/// add the values that are not in the vector yet, for records that are sent once per compilation
+/// of a crate but may come from several configurations
+fn extend_unique<T: PartialEq>(vec: &mut Vec<T>, values: Vec<T>) {
+    for value in values {
+        if !vec.contains(&value) {
+            vec.push(value);
+        }
+    }
+}
+
+/// data sent from the embedded compiler to the language server as a json string per line
+#[derive(Debug, Serialize, Deserialize)]
+enum Record {
+    Symbol(Url, Symbol),
+    Item(Url, Item),
+    /// an item declared in a dependency rather than a workspace member
+    DependencyItem(Url, Item),
+    Expr(Url, ExprType),
+    /// fields and methods of a type
+    TypeMembers(String, Vec<Member>),
+    /// children of a module, type or trait with the given path
+    PathChildren(String, Vec<Member>),
+    /// items that can be imported into the crate with the given name
+    Importables(String, Vec<Importable>),
+    /// signature of the function with the given path
+    Signature(String, Signature),
+    Call(Url, Call),
+    /// an identifier naming a trait, trait method or type
+    Reference(Url, Reference),
+    /// implementations of the trait, trait method or type with the given path
+    Implementations(String, Vec<Location>),
+    /// supertypes and subtypes of the trait or type with the given path
+    TypeRelations(String, TypeRelations),
+    Occurrence(Url, Occurrence),
+    ExitPoints(Url, ExitPoints),
+    /// the cfg options of a compilation and the files that it included
+    Cfgs(Vec<Url>, CfgOptions),
+    Runnable(Url, Runnable),
+}
+
+/// environment variable set when the embedded compiler is checking a dependency
+/// only item records are produced for dependencies since their bindings are never hovered
+const DEPENDENCY_ENV: &str = "MINIRA_DEPENDENCY";
+/// environment variable set when the embedded compiler checks a dependency without indexing it,
+/// which is still needed since the metadata of the regular compiler cannot be read by the embedded
+/// one
+const UNINDEXED_ENV: &str = "MINIRA_UNINDEXED";
+/// environment variable set to the package and target of a workspace member as json, which the
+/// embedded compiler records with the tests and binaries of the crate
+const TARGET_ENV: &str = "MINIRA_TARGET";
 
 struct CustomExecutor {
     members: HashSet<PackageId>,
-    tx: Sender<SymbolIpc>,
+    /// arguments appended to the compiler arguments of workspace members
+    extra_args: Vec<String>,
+    /// whether the items of dependencies are indexed for workspace symbols
+    dependency_symbols: bool,
+    /// whether crates are compiled again even if cargo considers them fresh
+    reload: bool,
+    tx: Sender<Record>,
 }
 
 impl Executor for CustomExecutor {
+    /// the records of a fresh crate are only sent when it is compiled, build scripts and proc
+    /// macros do not send any
+    fn force_rebuild(&self, unit: &Unit) -> bool {
+        self.reload && unit.mode.is_check()
+    }
+
     fn exec(
         &self,
         cmd: &ProcessBuilder,
         id: PackageId,
-        _target: &Target,
-        _mode: CompileMode,
+        target: &Target,
+        mode: CompileMode,
         _on_stdout_line: &mut dyn FnMut(&str) -> CargoResult<()>,
         _on_stderr_line: &mut dyn FnMut(&str) -> CargoResult<()>,
     ) -> CargoResult<()> {
-        if self.members.contains(&id) {
+        let member = self.members.contains(&id);
+
+        // dependencies that are only checked also go through the embedded compiler, which indexes
+        // their items when requested, build scripts and proc macros are left to the regular
+        // compiler
+        if member || mode.is_check() {
             // call this program again but with the rustc flag to use the embedded compiler
             let mut cmd = cmd.clone();
             let mut new_args = Vec::from([OsString::from("rustc")]);
             new_args.extend(cmd.get_args().cloned());
             cmd.args_replace(&new_args);
             cmd.program(env::current_exe()?);
+            if member {
+                cmd.args(&self.extra_args);
+                if let Some(target) = cargo_target(id, target) {
+                    cmd.env(TARGET_ENV, serde_json::to_string(&target)?);
+                }
+            } else if self.dependency_symbols {
+                cmd.env(DEPENDENCY_ENV, "1");
+            } else {
+                cmd.env(UNINDEXED_ENV, "1");
+            }
 
             let Ok(output) = cmd.exec_with_output() else {
                 return Ok(());
             };
 
-            // data is received as a json string
+            // data is received as a json string, lines that are not records are skipped rather
+            // than failing the compilation of the crate
             // TODO: read the stderr output for diagnostics
             for line in output.stdout.lines() {
-                self.tx.send(serde_json::from_str(&line?)?)?;
+                if let Ok(record) = serde_json::from_str(&line?) {
+                    self.tx.send(record)?;
+                }
             }
             Ok(())
         } else {
@@ -118,26 +425,129 @@ impl Executor for CustomExecutor {
     }
 }
 
+/// the package and target of the compilation, build scripts are never run by the code lenses
+fn cargo_target(id: PackageId, target: &Target) -> Option<CargoTarget> {
+    let kind = match target.kind() {
+        CargoTargetKind::Lib(..) => TargetKind::Lib,
+        CargoTargetKind::Bin => TargetKind::Bin,
+        CargoTargetKind::Test => TargetKind::Test,
+        CargoTargetKind::Bench => TargetKind::Bench,
+        CargoTargetKind::ExampleLib(..) | CargoTargetKind::ExampleBin => TargetKind::Example,
+        CargoTargetKind::CustomBuild => return None,
+    };
+    Some(CargoTarget {
+        package: id.name().to_string(),
+        kind,
+        name: target.name().to_owned(),
+    })
+}
+
 /// the embedded nightly compiler
 /// the first argument argument is automatically discarded, do not manually discard it
 /// a custom callback is used to retrieve type information
 pub fn compiler(args: &[String]) {
-    RunCompiler::new(args, &mut ThirCallback).run();
+    if env::var_os(UNINDEXED_ENV).is_some() {
+        RunCompiler::new(args, &mut Unindexed).run();
+        return;
+    }
+    let dependency = env::var_os(DEPENDENCY_ENV).is_some();
+    let target = env::var(TARGET_ENV)
+        .ok()
+        .and_then(|target| serde_json::from_str(&target).ok());
+    RunCompiler::new(args, &mut ThirCallback { dependency, target }).run();
 }
 
-struct ThirCallback;
+/// callbacks of a dependency that is only compiled for its metadata
+struct Unindexed;
+
+impl Callbacks for Unindexed {}
+
+struct ThirCallback {
+    /// whether the crate being compiled is a dependency rather than a workspace member
+    dependency: bool,
+    /// the package and target of a workspace member
+    target: Option<CargoTarget>,
+}
 
 impl Callbacks for ThirCallback {
     fn after_analysis(&mut self, _compiler: &Compiler, tcx: TyCtxt<'_>) -> Compilation {
-        tcx.hir()
-            .visit_all_item_likes_in_crate(&mut TypeVisitor { tcx });
+        let mut visitor = TypeVisitor {
+            tcx,
+            dependency: self.dependency,
+            target: self.target.take(),
+            maybe_typeck_results: None,
+            seen_types: HashSet::new(),
+            seen_paths: HashSet::new(),
+            seen_signatures: HashSet::new(),
+            seen_implementations: HashSet::new(),
+            writes: HashSet::new(),
+            exits: Vec::new(),
+        };
+        visitor.emit_path_children(CRATE_DEF_ID.to_def_id());
+        tcx.hir().visit_all_item_likes_in_crate(&mut visitor);
+
+        if !self.dependency {
+            emit(&Record::Importables(
+                tcx.crate_name(LOCAL_CRATE).to_string(),
+                member::importables(tcx),
+            ));
+            emit(&Record::Cfgs(local_files(tcx), cfg_options(tcx)));
+        }
 
         Compilation::Continue
     }
 }
 
+/// the source files of the crate being compiled
+fn local_files(tcx: TyCtxt<'_>) -> Vec<Url> {
+    tcx.sess
+        .source_map()
+        .files()
+        .iter()
+        .filter(|file| file.cnum == LOCAL_CRATE)
+        .filter_map(|file| match &file.name {
+            FileName::Real(RealFileName::LocalPath(path)) => file_url(path),
+            _ => None,
+        })
+        .collect()
+}
+
+/// the cfg options of the crate being compiled, such as `unix` or `feature = "gpu"`
+fn cfg_options(tcx: TyCtxt<'_>) -> CfgOptions {
+    tcx.sess
+        .psess
+        .config
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.map(|value| value.to_string())))
+        .collect()
+}
+
+/// the uri of a source file, whose path is relative to the manifest path
+fn file_url(path: &Path) -> Option<Url> {
+    // TODO: use the manifest path instead of the current directory
+    let current_dir = std::env::current_dir().ok()?;
+    Url::from_file_path(current_dir.join(path)).ok()
+}
+
 struct TypeVisitor<'tcx> {
     tcx: TyCtxt<'tcx>,
+    dependency: bool,
+    /// the package and target of a workspace member, whose runnables are recorded
+    target: Option<CargoTarget>,
+    /// type information of the body that is currently being visited
+    maybe_typeck_results: Option<&'tcx TypeckResults<'tcx>>,
+    /// types whose members were already sent
+    seen_types: HashSet<Ty<'tcx>>,
+    /// modules, types and traits whose children were already sent
+    seen_paths: HashSet<DefId>,
+    /// functions whose signatures were already sent
+    seen_signatures: HashSet<DefId>,
+    /// traits, trait methods and types whose implementations and relations were already sent
+    seen_implementations: HashSet<DefId>,
+    /// path expressions of bindings that are assigned to or mutably borrowed
+    writes: HashSet<HirId>,
+    /// exit points of the bodies that are currently being visited, the last one is the innermost
+    exits: Vec<Vec<Exit>>,
 }
 
 impl<'tcx> TypeVisitor<'tcx> {
@@ -160,13 +570,7 @@ impl<'tcx> TypeVisitor<'tcx> {
             return None;
         };
 
-        // the extracted file path is relative to the manifest path
-        // TODO: use the manifest path instead of the current directory
-        let Ok(current_dir) = std::env::current_dir() else {
-            return None;
-        };
-        let path = current_dir.join(path);
-        let uri = Url::from_file_path(path).ok()?;
+        let uri = file_url(path)?;
 
         // convert span to range
         // subtract 1 from the line and column numbers to account for 0-based indexing
@@ -185,11 +589,447 @@ impl<'tcx> TypeVisitor<'tcx> {
         Some((uri, range))
     }
 
+    /// location of the definition of the type
+    /// references, boxes, vectors and options are peeled to reach the type they contain
+    fn type_definition(&self, ty: Ty<'tcx>) -> Option<Location> {
+        let mut ty = ty.peel_refs();
+        loop {
+            let ty::Adt(adt, args) = ty.kind() else {
+                return None;
+            };
+            if adt.is_box()
+                || self.tcx.is_diagnostic_item(sym::Vec, adt.did())
+                || self.tcx.is_diagnostic_item(sym::Option, adt.did())
+            {
+                ty = args.type_at(0).peel_refs();
+                continue;
+            }
+
+            let (uri, range) = self.span_location(self.tcx.def_span(adt.did()))?;
+            return Some(Location { uri, range });
+        }
+    }
+
     /// get the type of the given hir ID of the variable
     fn get_type(&self, hir_id: HirId) -> Ty<'tcx> {
         let def_id = hir_id.owner.def_id;
         self.tcx.typeck(def_id).node_type(hir_id)
     }
+
+    fn snippet(&self, span: Span) -> String {
+        self.tcx
+            .sess
+            .source_map()
+            .span_to_snippet(span)
+            .unwrap_or_default()
+    }
+
+    /// send an item record for the item with the given spans of its body and identifier
+    fn emit_item(
+        &self,
+        def_id: LocalDefId,
+        name: String,
+        kind: SymbolKind,
+        detail: Option<String>,
+        span: Span,
+        ident_span: Span,
+    ) {
+        // skip items that were generated from macros and desugaring
+        if span.from_expansion() || ident_span.from_expansion() {
+            return;
+        }
+        let (Some((uri, range)), Some((ident_uri, selection_range))) =
+            (self.span_location(span), self.span_location(ident_span))
+        else {
+            return;
+        };
+        if uri != ident_uri {
+            return;
+        }
+
+        let item = Item {
+            name,
+            kind,
+            detail,
+            path: Some(self.def_path(def_id.to_def_id())),
+            range,
+            selection_range,
+        };
+        self.emit_doctest(def_id, span);
+
+        if self.dependency {
+            emit(&Record::DependencyItem(uri, item));
+        } else {
+            emit(&Record::Item(uri, item));
+        }
+    }
+
+    /// send a runnable record if the crate is a workspace member
+    fn emit_runnable(&self, kind: RunnableKind, path: String, span: Span) {
+        let Some(target) = &self.target else {
+            return;
+        };
+        let Some((uri, range)) = self.span_location(span) else {
+            return;
+        };
+
+        emit(&Record::Runnable(
+            uri,
+            Runnable {
+                kind,
+                path,
+                target: target.clone(),
+                range,
+            },
+        ));
+    }
+
+    /// send a runnable record for the test or bench described by the constant that `#[test]` and
+    /// `#[bench]` generate next to the function in test builds
+    fn emit_test(&self, i: &HirItem<'_>, body: BodyId) {
+        // the marker holds the path of the test and spans the attribute that generated it
+        let Some(marker) = self.tcx.get_attr(i.owner_id, sym::rustc_test_marker) else {
+            return;
+        };
+        let Some(path) = marker.value_str() else {
+            return;
+        };
+
+        // the `testfn` field of the constant is either `StaticTestFn(..)` or `StaticBenchFn(..)`
+        let ExprKind::Struct(_, fields, _) = self.tcx.hir().body(body).value.kind else {
+            return;
+        };
+        let bench = fields
+            .iter()
+            .filter(|field| field.ident.as_str() == "testfn")
+            .any(|field| match field.expr.kind {
+                ExprKind::Call(callee, _) => matches!(
+                    callee.kind,
+                    ExprKind::Path(QPath::Resolved(_, path))
+                        if path.segments.last().is_some_and(|segment| {
+                            segment.ident.as_str() == "StaticBenchFn"
+                        })
+                ),
+                _ => false,
+            });
+        let kind = if bench {
+            RunnableKind::Bench
+        } else {
+            RunnableKind::Test
+        };
+
+        self.emit_runnable(kind, path.to_string(), marker.span.with_hi(i.span.hi()));
+    }
+
+    /// send a runnable record if the documentation of the item has a doc test, which rustdoc only
+    /// runs for libraries
+    fn emit_doctest(&self, def_id: LocalDefId, span: Span) {
+        if self
+            .target
+            .as_ref()
+            .is_none_or(|target| target.kind != TargetKind::Lib)
+        {
+            return;
+        }
+        let attrs = self
+            .tcx
+            .hir()
+            .attrs(self.tcx.local_def_id_to_hir_id(def_id));
+        let doc = attrs
+            .iter()
+            .filter_map(rustc_hir::Attribute::doc_str)
+            .map(|doc| doc.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if !runnable::has_doctest(&doc) {
+            return;
+        }
+
+        // the range starts at the doc comments, inner doc comments of modules may be in another
+        // file than the item
+        let lo = attrs
+            .iter()
+            .filter(|attr| attr.style == AttrStyle::Outer && attr.doc_str().is_some())
+            .map(|attr| attr.span.lo())
+            .fold(span.lo(), std::cmp::min);
+        let path = with_no_trimmed_paths!(self.tcx.def_path_str(def_id));
+        self.emit_runnable(RunnableKind::Doctest, path, span.with_lo(lo));
+    }
+
+    /// full path of the definition, items of the local crate are prefixed with the crate name
+    fn def_path(&self, def_id: DefId) -> String {
+        let path = with_no_trimmed_paths!(self.tcx.def_path_str(def_id));
+        if !def_id.is_local() {
+            path
+        } else if path.is_empty() {
+            self.tcx.crate_name(LOCAL_CRATE).to_string()
+        } else {
+            format!("{}::{}", self.tcx.crate_name(LOCAL_CRATE), path)
+        }
+    }
+
+    /// sorted and deduplicated full paths of the definitions
+    fn def_paths(&self, def_ids: Vec<DefId>) -> Vec<String> {
+        let mut paths = def_ids
+            .into_iter()
+            .map(|def_id| self.def_path(def_id))
+            .collect::<Vec<_>>();
+        paths.sort_unstable();
+        paths.dedup();
+        paths
+    }
+
+    /// send the fields and methods of the type if they were not sent yet
+    fn emit_type_members(&mut self, ty: Ty<'tcx>) {
+        let ty = ty.peel_refs();
+        if self.dependency || ty.references_error() || !self.seen_types.insert(ty) {
+            return;
+        }
+
+        let members = member::type_members(self.tcx, ty);
+        if !members.is_empty() {
+            emit(&Record::TypeMembers(ty.to_string(), members));
+        }
+    }
+
+    /// send the children of the module, type or trait if they were not sent yet
+    fn emit_path_children(&mut self, def_id: DefId) {
+        if self.dependency || !self.seen_paths.insert(def_id) {
+            return;
+        }
+
+        let members = member::path_children(self.tcx, def_id);
+        if !members.is_empty() {
+            emit(&Record::PathChildren(self.def_path(def_id), members));
+        }
+    }
+
+    /// send the signature of the function if it was not sent yet
+    fn emit_signature(&mut self, def_id: DefId) {
+        if self.dependency || !self.seen_signatures.insert(def_id) {
+            return;
+        }
+
+        emit(&Record::Signature(
+            self.def_path(def_id),
+            signature::signature(self.tcx, def_id),
+        ));
+    }
+
+    /// send a reference for an identifier naming a trait, trait method or type, along with the
+    /// implementations and type relations of the named definition if they were not sent yet
+    fn emit_reference(&mut self, span: Span, def_id: DefId) {
+        if self.dependency
+            || span.from_expansion()
+            || !implementation::has_implementations(self.tcx, def_id)
+        {
+            return;
+        }
+        let Some((uri, range)) = self.span_location(span) else {
+            return;
+        };
+        let path = self.def_path(def_id);
+
+        if self.seen_implementations.insert(def_id) {
+            let locations = implementation::implementations(self.tcx, def_id)
+                .into_iter()
+                .filter_map(|id| self.span_location(self.tcx.def_span(id)))
+                .map(|(uri, range)| Location { uri, range })
+                .collect::<Vec<_>>();
+            if !locations.is_empty() {
+                emit(&Record::Implementations(path.clone(), locations));
+            }
+
+            let supertypes = self.def_paths(implementation::supertypes(self.tcx, def_id));
+            let subtypes = self.def_paths(implementation::subtypes(self.tcx, def_id));
+            if !supertypes.is_empty() || !subtypes.is_empty() {
+                emit(&Record::TypeRelations(
+                    path.clone(),
+                    TypeRelations {
+                        supertypes,
+                        subtypes,
+                    },
+                ));
+            }
+        }
+        emit(&Record::Reference(uri, Reference { range, path }));
+    }
+
+    /// the signature of a function, used as the detail of function items
+    fn fn_detail(&self, def_id: LocalDefId) -> String {
+        self.tcx.fn_sig(def_id).instantiate_identity().to_string()
+    }
+
+    /// the type of a field or constant, used as the detail of the item
+    fn type_detail(&self, def_id: LocalDefId) -> String {
+        self.tcx.type_of(def_id).instantiate_identity().to_string()
+    }
+}
+
+impl TypeVisitor<'_> {
+    /// send the called function of a call or method call expression
+    fn visit_call(&mut self, ex: &Expr<'_>) {
+        if self.dependency || ex.span.from_expansion() {
+            return;
+        }
+        let Some(results) = self.maybe_typeck_results else {
+            return;
+        };
+
+        let (callee, span, dynamic) = match ex.kind {
+            ExprKind::Call(
+                callee @ Expr {
+                    kind: ExprKind::Path(qpath),
+                    ..
+                },
+                _,
+            ) => (
+                results.qpath_res(qpath, callee.hir_id).opt_def_id(),
+                callee.span,
+                false,
+            ),
+            ExprKind::MethodCall(segment, receiver, ..) => (
+                results.type_dependent_def_id(ex.hir_id),
+                segment.ident.span,
+                results.expr_ty_adjusted(receiver).peel_refs().is_trait(),
+            ),
+            _ => return,
+        };
+        let Some(callee) =
+            callee.filter(|x| matches!(self.tcx.def_kind(x), DefKind::Fn | DefKind::AssocFn))
+        else {
+            return;
+        };
+        let Some((uri, range)) = self.span_location(span) else {
+            return;
+        };
+
+        self.emit_signature(callee);
+        emit(&Record::Call(
+            uri,
+            Call {
+                range,
+                callee: self.def_path(callee),
+                caller: self.def_path(results.hir_owner.to_def_id()),
+                dynamic,
+            },
+        ));
+    }
+}
+
+impl TypeVisitor<'_> {
+    /// remember the binding that is assigned to or mutably borrowed by the expression
+    fn mark_writes(&mut self, ex: &Expr<'_>) {
+        let mut place = match ex.kind {
+            ExprKind::Assign(place, ..)
+            | ExprKind::AssignOp(_, place, _)
+            | ExprKind::AddrOf(_, Mutability::Mut, place) => place,
+            // methods taking `&mut self` borrow the receiver mutably
+            ExprKind::MethodCall(_, receiver, ..)
+                if self.maybe_typeck_results.is_some_and(|results| {
+                    results.expr_adjustments(receiver).iter().any(|adjustment| {
+                        matches!(
+                            adjustment.kind,
+                            Adjust::Borrow(AutoBorrow::Ref(AutoBorrowMutability::Mut { .. }))
+                        )
+                    })
+                }) =>
+            {
+                receiver
+            }
+            _ => return,
+        };
+
+        // writing to a field or an element writes to the binding containing it
+        while let ExprKind::Field(base, _) | ExprKind::Index(base, ..) = place.kind {
+            place = base;
+        }
+        self.writes.insert(place.hir_id);
+    }
+
+    /// send a definition or use of the local binding
+    fn emit_occurrence(&self, span: Span, binding: HirId, kind: DocumentHighlightKind) {
+        if self.dependency || span.from_expansion() {
+            return;
+        }
+        let (Some((uri, range)), Some((_, binding))) = (
+            self.span_location(span),
+            self.span_location(self.tcx.hir().ident(binding).span),
+        ) else {
+            return;
+        };
+
+        emit(&Record::Occurrence(
+            uri,
+            Occurrence {
+                range,
+                binding,
+                kind,
+            },
+        ));
+    }
+
+    /// remember the `return` or `?` of the expression as an exit point of the innermost body
+    fn visit_exit(&mut self, ex: &Expr<'_>) {
+        let span = match ex.kind {
+            ExprKind::Ret(_) if !ex.span.from_expansion() => {
+                ex.span.with_hi(ex.span.lo() + BytePos(6))
+            }
+            ExprKind::Match(.., MatchSource::TryDesugar(_)) if !ex.span.from_expansion() => {
+                self.tcx.sess.source_map().end_point(ex.span)
+            }
+            _ => return,
+        };
+        let Some((_, range)) = self.span_location(span) else {
+            return;
+        };
+        if let Some(exits) = self.exits.last_mut() {
+            exits.push(Exit { range, tail: false });
+        }
+    }
+
+    /// send the exit points of a function body, closures and constants are skipped
+    fn emit_exit_points(&self, body: &Body<'_>, mut exits: Vec<Exit>) {
+        let owner = self.tcx.hir().body_owner_def_id(body.id());
+        if self.dependency || !matches!(self.tcx.hir().body_owner_kind(owner), BodyOwnerKind::Fn) {
+            return;
+        }
+
+        // the `fn` keyword is the last one before the name of the function
+        let Some(ident_span) = self.tcx.def_ident_span(owner) else {
+            return;
+        };
+        let header = self.tcx.def_span(owner).until(ident_span);
+        let Some(offset) = self.snippet(header).rfind("fn") else {
+            return;
+        };
+        #[expect(clippy::cast_possible_truncation)]
+        let lo = header.lo() + BytePos(offset as u32);
+        let Some((uri, keyword)) = self.span_location(header.with_lo(lo).with_hi(lo + BytePos(2)))
+        else {
+            return;
+        };
+
+        if let ExprKind::Block(block, _) = body.value.kind {
+            if let Some(tail) = block.expr.filter(|tail| !tail.span.from_expansion()) {
+                if let Some((_, range)) = self.span_location(tail.span) {
+                    exits.push(Exit { range, tail: true });
+                }
+            }
+        }
+        exits.sort_unstable_by_key(|exit| exit.range.start);
+
+        emit(&Record::ExitPoints(uri, ExitPoints { keyword, exits }));
+    }
+}
+
+/// serialize the record and send it to stdout
+fn emit(record: &Record) {
+    let mut stdout = std::io::stdout().lock();
+    stdout
+        .write_all(&serde_json::to_vec(record).expect("failed to serialize"))
+        .expect("failed to write to stdout");
+    stdout.write_all(b"\n").expect("failed to write to stdout");
 }
 
 /// visitor performs a nested walk through the hir to discover desired symbols
@@ -200,22 +1040,276 @@ impl<'tcx> Visitor<'tcx> for TypeVisitor<'tcx> {
         self.tcx.hir()
     }
 
+    fn visit_item(&mut self, i: &'tcx HirItem<'tcx>) -> Self::Result {
+        let def_id = i.owner_id.def_id;
+        let (kind, detail) = match i.kind {
+            ItemKind::Mod(..) => {
+                self.emit_path_children(def_id.to_def_id());
+                (SymbolKind::MODULE, None)
+            }
+            ItemKind::Struct(..) | ItemKind::Union(..) => {
+                self.emit_path_children(def_id.to_def_id());
+                (SymbolKind::STRUCT, None)
+            }
+            ItemKind::Enum(..) => {
+                self.emit_path_children(def_id.to_def_id());
+                (SymbolKind::ENUM, None)
+            }
+            ItemKind::Trait(..) => {
+                self.emit_path_children(def_id.to_def_id());
+                (SymbolKind::INTERFACE, None)
+            }
+            ItemKind::TraitAlias(..) => (SymbolKind::INTERFACE, None),
+            ItemKind::Fn(..) => {
+                self.emit_signature(def_id.to_def_id());
+                if self
+                    .tcx
+                    .entry_fn(())
+                    .is_some_and(|(entry, _)| entry == def_id.to_def_id())
+                {
+                    self.emit_runnable(RunnableKind::Main, "main".to_owned(), i.span);
+                }
+                (SymbolKind::FUNCTION, Some(self.fn_detail(def_id)))
+            }
+            ItemKind::Macro(..) => (SymbolKind::FUNCTION, None),
+            ItemKind::Const(_, _, body) => {
+                self.emit_test(i, body);
+                (SymbolKind::CONSTANT, Some(self.type_detail(def_id)))
+            }
+            ItemKind::Static(..) => (SymbolKind::VARIABLE, Some(self.type_detail(def_id))),
+            ItemKind::TyAlias(..) => (SymbolKind::TYPE_PARAMETER, Some(self.type_detail(def_id))),
+            ItemKind::Impl(imp) => {
+                // impl blocks do not have an identifier, so the self type is used instead
+                let name = match imp.of_trait {
+                    Some(trait_ref) => format!(
+                        "impl {} for {}",
+                        self.snippet(trait_ref.path.span),
+                        self.snippet(imp.self_ty.span)
+                    ),
+                    None => format!("impl {}", self.snippet(imp.self_ty.span)),
+                };
+                self.emit_item(
+                    def_id,
+                    name,
+                    SymbolKind::OBJECT,
+                    None,
+                    i.span,
+                    imp.self_ty.span,
+                );
+                return intravisit::walk_item(self, i);
+            }
+            _ => return intravisit::walk_item(self, i),
+        };
+        self.emit_reference(i.ident.span, def_id.to_def_id());
+        self.emit_item(
+            def_id,
+            i.ident.to_string(),
+            kind,
+            detail,
+            i.span,
+            i.ident.span,
+        );
+
+        intravisit::walk_item(self, i);
+    }
+
+    fn visit_trait_item(&mut self, ti: &'tcx TraitItem<'tcx>) -> Self::Result {
+        let def_id = ti.owner_id.def_id;
+        let (kind, detail) = match ti.kind {
+            TraitItemKind::Fn(..) => {
+                self.emit_signature(def_id.to_def_id());
+                self.emit_reference(ti.ident.span, def_id.to_def_id());
+                (SymbolKind::METHOD, Some(self.fn_detail(def_id)))
+            }
+            TraitItemKind::Const(..) => (SymbolKind::CONSTANT, Some(self.type_detail(def_id))),
+            TraitItemKind::Type(..) => (SymbolKind::TYPE_PARAMETER, None),
+        };
+        self.emit_item(
+            def_id,
+            ti.ident.to_string(),
+            kind,
+            detail,
+            ti.span,
+            ti.ident.span,
+        );
+
+        intravisit::walk_trait_item(self, ti);
+    }
+
+    fn visit_impl_item(&mut self, ii: &'tcx ImplItem<'tcx>) -> Self::Result {
+        let def_id = ii.owner_id.def_id;
+        let (kind, detail) = match ii.kind {
+            ImplItemKind::Fn(..) => {
+                self.emit_signature(def_id.to_def_id());
+                // the implementation of a trait method refers to the method of the trait
+                if let Some(trait_item_id) = self.tcx.associated_item(def_id).trait_item_def_id {
+                    self.emit_reference(ii.ident.span, trait_item_id);
+                }
+                (SymbolKind::METHOD, Some(self.fn_detail(def_id)))
+            }
+            ImplItemKind::Const(..) => (SymbolKind::CONSTANT, Some(self.type_detail(def_id))),
+            ImplItemKind::Type(..) => (SymbolKind::TYPE_PARAMETER, Some(self.type_detail(def_id))),
+        };
+        self.emit_item(
+            def_id,
+            ii.ident.to_string(),
+            kind,
+            detail,
+            ii.span,
+            ii.ident.span,
+        );
+
+        intravisit::walk_impl_item(self, ii);
+    }
+
+    fn visit_variant(&mut self, v: &'tcx Variant<'tcx>) -> Self::Result {
+        self.emit_item(
+            v.def_id,
+            v.ident.to_string(),
+            SymbolKind::ENUM_MEMBER,
+            None,
+            v.span,
+            v.ident.span,
+        );
+
+        intravisit::walk_variant(self, v);
+    }
+
+    fn visit_field_def(&mut self, s: &'tcx FieldDef<'tcx>) -> Self::Result {
+        // positional fields of tuple structs are not listed
+        if !s.is_positional() {
+            self.emit_item(
+                s.def_id,
+                s.ident.to_string(),
+                SymbolKind::FIELD,
+                Some(self.type_detail(s.def_id)),
+                s.span,
+                s.ident.span,
+            );
+        }
+
+        intravisit::walk_field_def(self, s);
+    }
+
+    fn visit_nested_body(&mut self, id: BodyId) -> Self::Result {
+        let old = self.maybe_typeck_results.replace(self.tcx.typeck_body(id));
+        let body = self.tcx.hir().body(id);
+        self.exits.push(Vec::new());
+        self.visit_body(body);
+        let exits = self.exits.pop().unwrap_or_default();
+        self.emit_exit_points(body, exits);
+        self.maybe_typeck_results = old;
+    }
+
+    fn visit_expr(&mut self, ex: &'tcx Expr<'tcx>) -> Self::Result {
+        self.mark_writes(ex);
+        intravisit::walk_expr(self, ex);
+        self.visit_call(ex);
+        self.visit_exit(ex);
+
+        // uses of local bindings
+        if let ExprKind::Path(QPath::Resolved(
+            None,
+            HirPath {
+                res: Res::Local(binding),
+                ..
+            },
+        )) = ex.kind
+        {
+            let kind = if self.writes.remove(&ex.hir_id) {
+                DocumentHighlightKind::WRITE
+            } else {
+                DocumentHighlightKind::READ
+            };
+            self.emit_occurrence(ex.span, *binding, kind);
+        }
+
+        // methods and associated functions resolved by type checking
+        if let ExprKind::MethodCall(segment, ..) | ExprKind::Path(QPath::TypeRelative(_, segment)) =
+            ex.kind
+        {
+            if let Some(def_id) = self
+                .maybe_typeck_results
+                .and_then(|results| results.type_dependent_def_id(ex.hir_id))
+            {
+                self.emit_reference(segment.ident.span, def_id);
+            }
+        }
+
+        // only expressions that can be the receiver of a field access or method call are recorded
+        if self.dependency
+            || ex.span.from_expansion()
+            || !matches!(
+                ex.kind,
+                ExprKind::Path(..)
+                    | ExprKind::Field(..)
+                    | ExprKind::MethodCall(..)
+                    | ExprKind::Call(..)
+                    | ExprKind::Index(..)
+            )
+        {
+            return;
+        }
+        let Some(ty) = self
+            .maybe_typeck_results
+            .and_then(|results| results.node_type_opt(ex.hir_id))
+        else {
+            return;
+        };
+        let Some((uri, range)) = self.span_location(ex.span) else {
+            return;
+        };
+
+        self.emit_type_members(ty);
+        emit(&Record::Expr(
+            uri,
+            ExprType {
+                range,
+                ty: ty.peel_refs().to_string(),
+            },
+        ));
+    }
+
+    fn visit_path(&mut self, path: &HirPath<'tcx>, _id: HirId) -> Self::Result {
+        // record the children of every module, type and trait named in a path
+        for segment in path.segments {
+            if let Res::Def(
+                DefKind::Mod | DefKind::Struct | DefKind::Union | DefKind::Enum | DefKind::Trait,
+                def_id,
+            ) = segment.res
+            {
+                self.emit_path_children(def_id);
+            }
+            if let Res::Def(_, def_id) = segment.res {
+                self.emit_reference(segment.ident.span, def_id);
+            }
+        }
+
+        intravisit::walk_path(self, path);
+    }
+
     fn visit_pat(&mut self, p: &'tcx Pat<'tcx>) -> Self::Result {
         intravisit::walk_pat(self, p);
 
         // skip symbols that were generated from macros and desugaring
-        if p.span.from_expansion() {
+        // and bindings of dependencies, which are never hovered
+        if p.span.from_expansion() || self.dependency {
             return;
         }
 
-        if let PatKind::Binding(_, _, ident, _) = p.kind {
+        if let PatKind::Binding(_, binding, ident, _) = p.kind {
+            self.emit_occurrence(ident.span, binding, DocumentHighlightKind::WRITE);
+
             let Some((uri, range)) = self.span_location(p.span) else {
                 return;
             };
+            let ty = self.get_type(p.hir_id);
+            self.emit_type_members(ty);
             let symbol = Symbol {
                 name: ident.name.to_string(),
-                ty: self.get_type(p.hir_id).to_string(),
+                ty: ty.to_string(),
                 range,
+                type_definition: self.type_definition(ty),
             };
 
             // all identifiers should be on the same line
@@ -225,12 +1319,7 @@ impl<'tcx> Visitor<'tcx> for TypeVisitor<'tcx> {
                 return;
             }
 
-            // serialize the data and send to stdout
-            let mut stdout = std::io::stdout().lock();
-            stdout
-                .write_all(&serde_json::to_vec(&(uri, symbol)).expect("failed to serialize"))
-                .expect("failed to write to stdout");
-            stdout.write_all(b"\n").expect("failed to write to stdout");
+            emit(&Record::Symbol(uri, symbol));
         }
     }
 }
@@ -0,0 +1,94 @@
+//! collection of the implementations used for go-to-implementation and type hierarchies with the
+//! embedded compiler
+
+use super::{rustc_hir, rustc_middle, rustc_span};
+
+use rustc_hir::def::DefKind;
+use rustc_middle::ty::fast_reject::{self, TreatParams};
+use rustc_middle::ty::{self, TyCtxt};
+use rustc_span::def_id::DefId;
+
+/// whether go-to-implementation is supported for the definition
+pub fn has_implementations(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    match tcx.def_kind(def_id) {
+        DefKind::Trait | DefKind::Struct | DefKind::Enum | DefKind::Union => true,
+        DefKind::AssocFn => tcx.trait_of_item(def_id).is_some(),
+        _ => false,
+    }
+}
+
+/// the impl blocks of a trait or type, or the implementations of a trait method
+pub fn implementations(tcx: TyCtxt<'_>, def_id: DefId) -> Vec<DefId> {
+    match tcx.def_kind(def_id) {
+        DefKind::Trait => tcx.all_impls(def_id).collect(),
+        DefKind::AssocFn => {
+            let Some(trait_id) = tcx.trait_of_item(def_id) else {
+                return Vec::new();
+            };
+            // impls that use the default implementation of the method are skipped
+            tcx.all_impls(trait_id)
+                .filter_map(|impl_id| tcx.impl_item_implementor_ids(impl_id).get(&def_id).copied())
+                .collect()
+        }
+        DefKind::Struct | DefKind::Enum | DefKind::Union => {
+            let mut impls = tcx.inherent_impls(def_id).to_vec();
+
+            // blanket implementations are skipped since they apply to almost every type
+            let ty = tcx.type_of(def_id).instantiate_identity();
+            if let Some(simplified) = fast_reject::simplify_type(tcx, ty, TreatParams::AsRigid) {
+                for trait_id in tcx.all_traits() {
+                    if let Some(trait_impls) = tcx
+                        .trait_impls_of(trait_id)
+                        .non_blanket_impls()
+                        .get(&simplified)
+                    {
+                        impls.extend(trait_impls);
+                    }
+                }
+            }
+            impls
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// traits extended by the trait, or traits implemented by the type
+pub fn supertypes(tcx: TyCtxt<'_>, def_id: DefId) -> Vec<DefId> {
+    match tcx.def_kind(def_id) {
+        DefKind::Trait => supertraits(tcx, def_id),
+        DefKind::Struct | DefKind::Enum | DefKind::Union => implementations(tcx, def_id)
+            .into_iter()
+            .filter_map(|impl_id| tcx.trait_id_of_impl(impl_id))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// traits extending the trait and types implementing it
+pub fn subtypes(tcx: TyCtxt<'_>, def_id: DefId) -> Vec<DefId> {
+    if tcx.def_kind(def_id) != DefKind::Trait {
+        return Vec::new();
+    }
+
+    let mut subtypes = tcx
+        .all_traits()
+        .filter(|trait_id| supertraits(tcx, *trait_id).contains(&def_id))
+        .collect::<Vec<_>>();
+    // blanket implementations do not have a type that can be listed
+    subtypes.extend(tcx.all_impls(def_id).filter_map(|impl_id| {
+        tcx.type_of(impl_id)
+            .instantiate_identity()
+            .ty_adt_def()
+            .map(ty::AdtDef::did)
+    }));
+    subtypes
+}
+
+fn supertraits(tcx: TyCtxt<'_>, def_id: DefId) -> Vec<DefId> {
+    tcx.explicit_super_predicates_of(def_id)
+        .skip_binder()
+        .iter()
+        .filter_map(|(clause, _)| clause.as_trait_clause())
+        .map(ty::PolyTraitPredicate::def_id)
+        .collect()
+}
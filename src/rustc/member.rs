@@ -0,0 +1,277 @@
+//! collection of the members used for code completion with the embedded compiler
+
+use super::{rustc_hir, rustc_middle, rustc_session, rustc_span};
+
+use rustc_hir::def::{DefKind, Res};
+use rustc_middle::ty::fast_reject::{self, TreatParams};
+use rustc_middle::ty::print::with_no_trimmed_paths;
+use rustc_middle::ty::{self, AssocKind, Ty, TyCtxt};
+use rustc_session::cstore::ExternCrate;
+use rustc_span::def_id::DefId;
+use rustc_span::hygiene::MacroKind;
+use tower_lsp::lsp_types::CompletionItemKind;
+
+use crate::member::{Importable, Member};
+
+/// fields and methods of the given type, including the methods of traits implemented for it
+pub fn type_members<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Vec<Member> {
+    let mut members = Vec::new();
+
+    if let ty::Adt(adt, _) = ty.kind() {
+        if adt.is_struct() || adt.is_union() {
+            for field in &adt.non_enum_variant().fields {
+                if !visible(tcx, field.did) {
+                    continue;
+                }
+                members.push(Member {
+                    name: field.name.to_string(),
+                    kind: CompletionItemKind::FIELD,
+                    detail: Some(tcx.type_of(field.did).instantiate_identity().to_string()),
+                    documentation: documentation(tcx, field.did),
+                    has_self: false,
+                });
+            }
+        }
+    }
+
+    let Some(simplified) = fast_reject::simplify_type(tcx, ty, TreatParams::AsRigid) else {
+        return members;
+    };
+
+    // inherent methods
+    let inherent_impls = match ty.kind() {
+        ty::Adt(adt, _) => tcx.inherent_impls(adt.did()),
+        _ => tcx.incoherent_impls(simplified),
+    };
+    for impl_id in inherent_impls {
+        assoc_members(tcx, *impl_id, &mut members);
+    }
+
+    // methods of traits that are implemented for the type
+    // blanket implementations are skipped since they apply to almost every type
+    for trait_id in tcx.all_traits() {
+        if visible(tcx, trait_id)
+            && tcx
+                .trait_impls_of(trait_id)
+                .non_blanket_impls()
+                .contains_key(&simplified)
+        {
+            assoc_members(tcx, trait_id, &mut members);
+        }
+    }
+
+    members
+}
+
+/// children of the given module, type or trait that can be named with a path
+pub fn path_children(tcx: TyCtxt<'_>, def_id: DefId) -> Vec<Member> {
+    let mut members = Vec::new();
+
+    match tcx.def_kind(def_id) {
+        DefKind::Mod => {
+            let children = match def_id.as_local() {
+                Some(local) => tcx.module_children_local(local),
+                None => tcx.module_children(def_id),
+            };
+            for child in children {
+                let Res::Def(kind, child_id) = child.res else {
+                    continue;
+                };
+                let Some(completion_kind) = completion_kind(kind) else {
+                    continue;
+                };
+                if !def_id.is_local() && !child.vis.is_public() {
+                    continue;
+                }
+                let detail = matches!(kind, DefKind::Fn)
+                    .then(|| tcx.fn_sig(child_id).instantiate_identity().to_string());
+
+                members.push(Member {
+                    name: child.ident.to_string(),
+                    kind: completion_kind,
+                    detail,
+                    documentation: documentation(tcx, child_id),
+                    has_self: false,
+                });
+            }
+        }
+        DefKind::Enum => {
+            for variant in tcx.adt_def(def_id).variants() {
+                members.push(Member {
+                    name: variant.name.to_string(),
+                    kind: CompletionItemKind::ENUM_MEMBER,
+                    detail: None,
+                    documentation: documentation(tcx, variant.def_id),
+                    has_self: false,
+                });
+            }
+            for impl_id in tcx.inherent_impls(def_id) {
+                assoc_members(tcx, *impl_id, &mut members);
+            }
+        }
+        DefKind::Struct | DefKind::Union => {
+            for impl_id in tcx.inherent_impls(def_id) {
+                assoc_members(tcx, *impl_id, &mut members);
+            }
+        }
+        DefKind::Trait => assoc_members(tcx, def_id, &mut members),
+        _ => {}
+    }
+
+    members
+}
+
+/// add the associated items of an impl block or trait to the members
+fn assoc_members(tcx: TyCtxt<'_>, container: DefId, members: &mut Vec<Member>) {
+    for item in tcx.associated_items(container).in_definition_order() {
+        // skip synthesized items and private items of other crates
+        if item.opt_rpitit_info.is_some() || !visible(tcx, item.def_id) {
+            continue;
+        }
+
+        let (kind, detail) = match item.kind {
+            AssocKind::Fn => (
+                if item.fn_has_self_parameter {
+                    CompletionItemKind::METHOD
+                } else {
+                    CompletionItemKind::FUNCTION
+                },
+                tcx.fn_sig(item.def_id).instantiate_identity().to_string(),
+            ),
+            AssocKind::Const => (
+                CompletionItemKind::CONSTANT,
+                tcx.type_of(item.def_id).instantiate_identity().to_string(),
+            ),
+            AssocKind::Type => (CompletionItemKind::TYPE_PARAMETER, String::new()),
+        };
+
+        members.push(Member {
+            name: item.name.to_string(),
+            kind,
+            detail: (!detail.is_empty()).then_some(detail),
+            documentation: documentation(tcx, item.def_id),
+            has_self: item.fn_has_self_parameter,
+        });
+    }
+}
+
+fn completion_kind(kind: DefKind) -> Option<CompletionItemKind> {
+    Some(match kind {
+        DefKind::Mod => CompletionItemKind::MODULE,
+        DefKind::Struct | DefKind::Union | DefKind::ForeignTy => CompletionItemKind::STRUCT,
+        DefKind::Enum => CompletionItemKind::ENUM,
+        DefKind::Variant => CompletionItemKind::ENUM_MEMBER,
+        DefKind::Trait | DefKind::TraitAlias => CompletionItemKind::INTERFACE,
+        DefKind::TyAlias => CompletionItemKind::TYPE_PARAMETER,
+        DefKind::Fn | DefKind::Macro(_) => CompletionItemKind::FUNCTION,
+        DefKind::Const => CompletionItemKind::CONSTANT,
+        DefKind::Static { .. } => CompletionItemKind::VARIABLE,
+        _ => return None,
+    })
+}
+
+/// items of other crates are only visible if they are public
+fn visible(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    def_id.is_local() || tcx.visibility(def_id).is_public()
+}
+
+/// the doc comment of the item with the leading space of each line removed
+pub fn documentation(tcx: TyCtxt<'_>, def_id: DefId) -> Option<String> {
+    let docs = tcx
+        .get_attrs_unchecked(def_id)
+        .iter()
+        .filter_map(rustc_hir::Attribute::doc_str)
+        .flat_map(|doc| {
+            doc.as_str()
+                .lines()
+                .map(|line| line.strip_prefix(' ').unwrap_or(line).to_owned())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    (!docs.is_empty()).then(|| docs.join("\n"))
+}
+
+/// items of direct dependencies and of the local crate that can be imported with a `use`
+pub fn importables(tcx: TyCtxt<'_>) -> Vec<Importable> {
+    let mut importables = Vec::new();
+
+    // items of other crates are imported by the path they are visible at, which must start with a
+    // direct dependency since items of `alloc` are visible through `std`, for example
+    let direct_crates = tcx
+        .crates(())
+        .iter()
+        .filter(|krate| {
+            tcx.extern_crate(**krate)
+                .is_some_and(ExternCrate::is_direct)
+        })
+        .map(|krate| tcx.crate_name(*krate).to_string())
+        .collect::<Vec<_>>();
+    let visible_parents =
+        tcx.with_stable_hashing_context(|hcx| tcx.visible_parent_map(()).to_sorted(&hcx, true));
+    for (&def_id, _) in visible_parents {
+        let Some(kind) = importable_kind(tcx.def_kind(def_id)) else {
+            continue;
+        };
+        if !tcx.visibility(def_id).is_public()
+            || tcx.is_doc_hidden(def_id)
+            || tcx
+                .lookup_stability(def_id)
+                .is_some_and(|stability| stability.is_unstable())
+        {
+            continue;
+        }
+
+        let path = with_no_trimmed_paths!(tcx.def_path_str(def_id));
+        if path.contains(['<', '{'])
+            || !path
+                .split("::")
+                .next()
+                .is_some_and(|krate| direct_crates.iter().any(|x| x == krate))
+        {
+            continue;
+        }
+        importables.push(Importable {
+            name: tcx.item_name(def_id).to_string(),
+            path,
+            kind,
+        });
+    }
+
+    // items of the local crate declared directly in a module
+    for def_id in tcx.hir_crate_items(()).definitions() {
+        let Some(kind) = importable_kind(tcx.def_kind(def_id)) else {
+            continue;
+        };
+        if tcx.def_kind(tcx.local_parent(def_id)) != DefKind::Mod {
+            continue;
+        }
+        importables.push(Importable {
+            name: tcx.item_name(def_id.to_def_id()).to_string(),
+            path: format!(
+                "crate::{}",
+                with_no_trimmed_paths!(tcx.def_path_str(def_id))
+            ),
+            kind,
+        });
+    }
+
+    importables.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+    importables.dedup();
+    importables
+}
+
+fn importable_kind(kind: DefKind) -> Option<CompletionItemKind> {
+    match kind {
+        DefKind::Struct
+        | DefKind::Union
+        | DefKind::Enum
+        | DefKind::Trait
+        | DefKind::TyAlias
+        | DefKind::Fn
+        | DefKind::Const
+        | DefKind::Static { .. }
+        | DefKind::Macro(MacroKind::Bang) => completion_kind(kind),
+        _ => None,
+    }
+}
@@ -0,0 +1,97 @@
+//! rendering of function signatures for signature help with the embedded compiler
+
+use super::{rustc_hir, rustc_middle, rustc_span};
+
+use rustc_hir::LangItem;
+use rustc_middle::ty::{GenericParamDefKind, TyCtxt};
+use rustc_span::def_id::DefId;
+
+use super::member;
+use crate::signature::Signature;
+
+/// render the signature of the function with its generics and where clauses
+pub fn signature(tcx: TyCtxt<'_>, def_id: DefId) -> Signature {
+    let sig = tcx.fn_sig(def_id).instantiate_identity().skip_binder();
+    let names = tcx.fn_arg_names(def_id);
+    let mut label = format!("fn {}", tcx.item_name(def_id));
+
+    // generic parameters, excluding those introduced by `impl Trait` arguments
+    let generics = tcx
+        .generics_of(def_id)
+        .own_params
+        .iter()
+        .filter_map(|param| match param.kind {
+            GenericParamDefKind::Type {
+                synthetic: true, ..
+            } => None,
+            GenericParamDefKind::Const { .. } => Some(format!(
+                "const {}: {}",
+                param.name,
+                tcx.type_of(param.def_id).instantiate_identity()
+            )),
+            _ => Some(param.name.to_string()),
+        })
+        .collect::<Vec<_>>();
+    if !generics.is_empty() {
+        label.push_str(&format!("<{}>", generics.join(", ")));
+    }
+
+    // parameters and their offsets in the label
+    label.push('(');
+    let mut parameters = Vec::new();
+    for (idx, ty) in sig.inputs().iter().enumerate() {
+        if idx > 0 {
+            label.push_str(", ");
+        }
+        let start = char_len(&label);
+        let name = names
+            .get(idx)
+            .map_or_else(|| "_".to_owned(), ToString::to_string);
+        // the receiver is shown the way it is usually written
+        let param = match (name.as_str(), ty.to_string().as_str()) {
+            ("self", "Self") => "self".to_owned(),
+            ("self", "&Self") => "&self".to_owned(),
+            ("self", "&mut Self") => "&mut self".to_owned(),
+            (_, ty) => format!("{name}: {ty}"),
+        };
+        label.push_str(&param);
+        parameters.push([start, char_len(&label)]);
+    }
+    label.push(')');
+
+    let output = sig.output();
+    if !output.is_unit() {
+        label.push_str(&format!(" -> {output}"));
+    }
+
+    // the implicit `Sized` bounds and the bounds of `impl Trait` arguments are omitted
+    let clauses = tcx
+        .predicates_of(def_id)
+        .predicates
+        .iter()
+        .filter(|(clause, _)| {
+            clause
+                .as_trait_clause()
+                .is_none_or(|x| !tcx.is_lang_item(x.def_id(), LangItem::Sized))
+        })
+        .map(|(clause, _)| clause.to_string())
+        .filter(|clause| !clause.starts_with("impl "))
+        .collect::<Vec<_>>();
+    if !clauses.is_empty() {
+        label.push_str(&format!(" where {}", clauses.join(", ")));
+    }
+
+    Signature {
+        label,
+        parameters,
+        documentation: member::documentation(tcx, def_id),
+        has_self: tcx
+            .opt_associated_item(def_id)
+            .is_some_and(|item| item.fn_has_self_parameter),
+    }
+}
+
+#[expect(clippy::cast_possible_truncation)]
+fn char_len(s: &str) -> u32 {
+    s.chars().count() as u32
+}
@@ -0,0 +1,108 @@
+//! code related to the signature table used for signature help and call hierarchies
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::*;
+
+#[derive(Debug, Default)]
+pub struct SignatureTable {
+    /// signatures of functions, keyed by their full path
+    pub signatures: HashMap<String, Signature>,
+    /// calls in each file
+    /// invariant:
+    /// - the calls in the same file are sorted by the end of the range of the callee
+    pub calls: HashMap<Url, Vec<Call>>,
+}
+
+impl SignatureTable {
+    pub fn merge_replace(&mut self, other: Self) {
+        self.signatures.extend(other.signatures);
+        for (url, calls) in other.calls {
+            self.calls.entry(url).insert_entry(calls);
+        }
+    }
+
+    /// drop the results of the files that `keep` rejects, such as files that were deleted
+    pub fn retain_files(&mut self, keep: impl Fn(&Url) -> bool) {
+        self.calls.retain(|url, _| keep(url));
+    }
+
+    /// find the function called by the callee that ends at the given position
+    pub fn callee(&self, url: &Url, end: Position) -> Option<(&str, &Signature)> {
+        let calls = self.calls.get(url)?;
+        let idx = calls.partition_point(|call| call.range.end < end);
+        let call = calls.get(idx).filter(|call| call.range.end == end)?;
+
+        self.signatures
+            .get_key_value(&call.callee)
+            .map(|(path, signature)| (path.as_str(), signature))
+    }
+
+    /// find the call whose callee contains the given position
+    pub fn call_at(&self, url: &Url, position: Position) -> Option<&Call> {
+        let calls = self.calls.get(url)?;
+        let idx = calls.partition_point(|call| call.range.end < position);
+
+        calls[idx..]
+            .iter()
+            .take_while(|call| call.range.end.line == position.line)
+            .find(|call| call.range.start <= position)
+    }
+
+    /// calls made to the function with the given path, grouped by the file of the caller
+    pub fn incoming_calls<'a>(
+        &'a self,
+        path: &'a str,
+    ) -> impl Iterator<Item = (&'a Url, &'a Call)> {
+        self.calls.iter().flat_map(move |(url, calls)| {
+            calls
+                .iter()
+                .filter(move |call| call.callee == path)
+                .map(move |call| (url, call))
+        })
+    }
+
+    /// calls made by the function with the given path
+    pub fn outgoing_calls<'a>(&'a self, path: &'a str) -> impl Iterator<Item = &'a Call> {
+        self.calls
+            .values()
+            .flatten()
+            .filter(move |call| call.caller == path)
+    }
+
+    /// find all functions with the given name
+    pub fn by_name(&self, name: &str) -> impl Iterator<Item = (&str, &Signature)> {
+        let suffix = format!("::{name}");
+        self.signatures
+            .iter()
+            .filter(move |(path, _)| path.ends_with(&suffix))
+            .map(|(path, signature)| (path.as_str(), signature))
+    }
+}
+
+/// the rendered signature of a function
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Signature {
+    /// the signature including generics and where clauses, such as `fn new(x: i32) -> Point`
+    pub label: String,
+    /// offsets of each parameter in the label in chars, encoded when they are sent to the client
+    pub parameters: Vec<[u32; 2]>,
+    /// documentation of the function in markdown
+    pub documentation: Option<String>,
+    /// whether the first parameter is `self`, which is skipped by the method call syntax
+    pub has_self: bool,
+}
+
+/// a call to a function recorded by the embedded compiler
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Call {
+    /// range of the callee, such as `Point::new` or the name of a method
+    pub range: Range,
+    /// full path of the called function
+    pub callee: String,
+    /// full path of the function whose body contains the call
+    pub caller: String,
+    /// whether the method is called through a trait object
+    pub dynamic: bool,
+}
@@ -20,6 +20,11 @@ impl SymbolTable {
         }
     }
 
+    /// drop the results of the files that `keep` rejects, such as files that were deleted
+    pub fn retain_files(&mut self, keep: impl Fn(&Url) -> bool) {
+        self.inner.retain(|url, _| keep(url));
+    }
+
     /// query the symbol table for the symbol at the given position using binary search since the
     /// data is sorted
     pub fn query(&self, url: &Url, position: Position) -> Option<Symbol> {
@@ -32,6 +37,7 @@ impl SymbolTable {
                 start: position,
                 end: position,
             },
+            type_definition: None,
         });
 
         // try and retrieve the symbol and check to ensure the range is valid
@@ -48,6 +54,8 @@ pub struct Symbol {
     pub name: String,
     pub ty: String,
     pub range: Range,
+    /// location of the definition of the type, after peeling references and wrapper types
+    pub type_definition: Option<Location>,
 }
 
 impl PartialOrd for Symbol {
@@ -0,0 +1,255 @@
+//! code for parsing documents with the bundled rustc parser without performing type checking
+//! the results are available immediately, unlike those of a full workspace check
+
+extern crate rustc_ast;
+extern crate rustc_driver;
+extern crate rustc_error_messages;
+extern crate rustc_errors;
+extern crate rustc_lexer;
+extern crate rustc_parse;
+extern crate rustc_session;
+extern crate rustc_span;
+
+mod cfg;
+mod error;
+mod folding;
+mod selection;
+
+use std::sync::Arc;
+
+use rustc_ast::visit::{self, AssocCtxt, Visitor};
+use rustc_ast::{AssocItem, AssocItemKind, FieldDef, Item as AstItem, ItemKind, Variant};
+use rustc_errors::DiagCtxt;
+use rustc_session::parse::ParseSess;
+use rustc_span::source_map::{FilePathMapping, SourceMap};
+use rustc_span::{FileName, Span};
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticSeverity, FoldingRange, Position, Range, SelectionRange, SymbolKind,
+};
+
+use crate::item::{self, Item};
+
+pub use cfg::CfgRegion;
+
+/// results of parsing an opened document, which are updated on every change
+#[derive(Debug, Default)]
+pub struct Syntax {
+    /// items declared in the document
+    /// `None` if the parser could not recover from a syntax error
+    pub items: Option<Vec<Item>>,
+    pub folding_ranges: Vec<FoldingRange>,
+    /// ranges of the syntax nodes that a selection can expand to
+    pub selection_ranges: Vec<Range>,
+    /// syntax nodes with a cfg attribute
+    pub cfg_regions: Vec<CfgRegion>,
+    /// syntax errors reported by the parser
+    pub errors: Vec<Diagnostic>,
+}
+
+impl Syntax {
+    /// the ranges that the selection expands to at the position, from the innermost syntax node
+    /// containing the position to the whole file
+    pub fn selection_range(&self, position: Position) -> SelectionRange {
+        selection::selection_range(&self.selection_ranges, position)
+    }
+}
+
+/// parse the source text
+/// comments and regions can be folded even if the parser could not recover from a syntax error
+pub fn parse(source: &str) -> Syntax {
+    let mut syntax = parse_aux(source.to_owned()).unwrap_or_default();
+    syntax
+        .folding_ranges
+        .extend(folding::comment_ranges(source));
+    syntax
+        .folding_ranges
+        .sort_unstable_by_key(|range| (range.start_line, range.end_line));
+    syntax
+}
+
+fn parse_aux(source: String) -> Option<Syntax> {
+    rustc_driver::catch_fatal_errors(|| {
+        rustc_span::create_default_session_globals_then(|| {
+            // errors are collected to be published as diagnostics instead of being printed
+            let errors = error::Errors::default();
+            let dcx = DiagCtxt::new(Box::new(error::ErrorCollector::new(Arc::clone(&errors))))
+                .disable_warnings();
+            // the parser requires a shared source map even though it stays on this thread
+            #[expect(clippy::arc_with_non_send_sync)]
+            let psess =
+                ParseSess::with_dcx(dcx, Arc::new(SourceMap::new(FilePathMapping::empty())));
+
+            let krate = match rustc_parse::new_parser_from_source_str(
+                &psess,
+                FileName::Custom("minira".to_owned()),
+                source,
+            ) {
+                Ok(mut parser) => parser.parse_crate_mod().map_err(|diag| diag.emit()).ok(),
+                Err(diags) => {
+                    for diag in diags {
+                        diag.emit();
+                    }
+                    None
+                }
+            };
+
+            let mut syntax = Syntax::default();
+            if let Some(krate) = krate {
+                let mut visitor = ItemVisitor {
+                    psess: &psess,
+                    items: Vec::new(),
+                };
+                visit::walk_crate(&mut visitor, &krate);
+                let mut items = visitor.items;
+                item::sort_items(&mut items);
+
+                syntax.items = Some(items);
+                syntax.folding_ranges = folding::syntax_ranges(&psess, &krate);
+                syntax.selection_ranges = selection::syntax_ranges(&psess, &krate);
+                syntax.cfg_regions = cfg::cfg_regions(&psess, &krate);
+            }
+            syntax.errors = errors
+                .lock()
+                .expect("poisoned")
+                .drain(..)
+                .filter_map(|(message, span)| {
+                    Some(Diagnostic {
+                        range: span_range(&psess, span?)?,
+                        severity: Some(DiagnosticSeverity::ERROR),
+                        source: Some(env!("CARGO_PKG_NAME").to_string()),
+                        message,
+                        ..Default::default()
+                    })
+                })
+                .collect();
+
+            syntax
+        })
+    })
+    .ok()
+}
+
+struct ItemVisitor<'a> {
+    psess: &'a ParseSess,
+    items: Vec<Item>,
+}
+
+/// convert a span of the parsed source text to a range
+fn span_range(psess: &ParseSess, span: Span) -> Option<Range> {
+    // line and columns in the source file
+    let (Some(_), lo_line, lo_col, hi_line, hi_col) =
+        psess.source_map().span_to_location_info(span)
+    else {
+        return None;
+    };
+
+    // subtract 1 from the line and column numbers to account for 0-based indexing
+    #[allow(clippy::cast_possible_truncation)]
+    Some(Range {
+        start: Position {
+            line: lo_line as u32 - 1,
+            character: lo_col as u32 - 1,
+        },
+        end: Position {
+            line: hi_line as u32 - 1,
+            character: hi_col as u32 - 1,
+        },
+    })
+}
+
+impl ItemVisitor<'_> {
+    fn push(&mut self, name: String, kind: SymbolKind, span: Span, ident_span: Span) {
+        // skip items that were generated from macros
+        if span.from_expansion() || ident_span.from_expansion() {
+            return;
+        }
+        let (Some(range), Some(selection_range)) = (
+            span_range(self.psess, span),
+            span_range(self.psess, ident_span),
+        ) else {
+            return;
+        };
+
+        self.items.push(Item {
+            name,
+            kind,
+            detail: None,
+            path: None,
+            range,
+            selection_range,
+        });
+    }
+
+    fn snippet(&self, span: Span) -> String {
+        self.psess
+            .source_map()
+            .span_to_snippet(span)
+            .unwrap_or_default()
+    }
+}
+
+impl<'ast> Visitor<'ast> for ItemVisitor<'_> {
+    fn visit_item(&mut self, i: &'ast AstItem) {
+        let kind = match &i.kind {
+            ItemKind::Mod(..) => Some(SymbolKind::MODULE),
+            ItemKind::Struct(..) | ItemKind::Union(..) => Some(SymbolKind::STRUCT),
+            ItemKind::Enum(..) => Some(SymbolKind::ENUM),
+            ItemKind::Trait(..) | ItemKind::TraitAlias(..) => Some(SymbolKind::INTERFACE),
+            ItemKind::Fn(..) | ItemKind::MacroDef(..) => Some(SymbolKind::FUNCTION),
+            ItemKind::Const(..) => Some(SymbolKind::CONSTANT),
+            ItemKind::Static(..) => Some(SymbolKind::VARIABLE),
+            ItemKind::TyAlias(..) => Some(SymbolKind::TYPE_PARAMETER),
+            ItemKind::Impl(imp) => {
+                // impl blocks do not have an identifier, so the self type is used instead
+                let name = match &imp.of_trait {
+                    Some(trait_ref) => format!(
+                        "impl {} for {}",
+                        self.snippet(trait_ref.path.span),
+                        self.snippet(imp.self_ty.span)
+                    ),
+                    None => format!("impl {}", self.snippet(imp.self_ty.span)),
+                };
+                self.push(name, SymbolKind::OBJECT, i.span, imp.self_ty.span);
+                None
+            }
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            self.push(i.ident.to_string(), kind, i.span, i.ident.span);
+        }
+
+        visit::walk_item(self, i);
+    }
+
+    fn visit_assoc_item(&mut self, i: &'ast AssocItem, ctxt: AssocCtxt) {
+        let kind = match &i.kind {
+            AssocItemKind::Fn(..) => Some(SymbolKind::METHOD),
+            AssocItemKind::Const(..) => Some(SymbolKind::CONSTANT),
+            AssocItemKind::Type(..) => Some(SymbolKind::TYPE_PARAMETER),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            self.push(i.ident.to_string(), kind, i.span, i.ident.span);
+        }
+
+        visit::walk_assoc_item(self, i, ctxt);
+    }
+
+    fn visit_variant(&mut self, v: &'ast Variant) {
+        self.push(
+            v.ident.to_string(),
+            SymbolKind::ENUM_MEMBER,
+            v.span,
+            v.ident.span,
+        );
+        visit::walk_variant(self, v);
+    }
+
+    fn visit_field_def(&mut self, s: &'ast FieldDef) {
+        // positional fields of tuple structs are not listed
+        if let Some(ident) = s.ident {
+            self.push(ident.to_string(), SymbolKind::FIELD, s.span, ident.span);
+        }
+        visit::walk_field_def(self, s);
+    }
+}
@@ -0,0 +1,183 @@
+//! code behind `#[cfg(...)]` attributes, which is marked as inactive when no compilation of the
+//! file enables it
+
+use super::{rustc_ast, rustc_session, rustc_span};
+
+use rustc_ast::visit::{self, AssocCtxt, Visitor};
+use rustc_ast::{
+    Arm, AssocItem, AttrStyle, Attribute, Crate, Expr, ExprField, FieldDef, ForeignItem,
+    GenericParam, Item, LitKind, Local, MetaItemInner, MetaItemKind, Param, PatField, Variant,
+};
+use rustc_session::parse::ParseSess;
+use rustc_span::{sym, Span};
+use tower_lsp::lsp_types::Range;
+
+use crate::cfg::Cfg;
+
+/// a syntax node with a `#[cfg(...)]` attribute
+#[derive(Debug, Clone)]
+pub struct CfgRegion {
+    /// range of the node including its attributes
+    pub range: Range,
+    pub cfg: Cfg,
+    /// the predicate as written, such as `feature = "gpu"`
+    pub predicate: String,
+}
+
+/// the nodes with a cfg attribute, sorted by their start
+/// nodes inside other nodes with a cfg attribute are included
+pub fn cfg_regions(psess: &ParseSess, krate: &Crate) -> Vec<CfgRegion> {
+    let mut visitor = CfgVisitor {
+        psess,
+        regions: Vec::new(),
+    };
+    // an inner attribute of the file applies to the whole file
+    let inner = krate
+        .attrs
+        .iter()
+        .filter(|attr| attr.style == AttrStyle::Inner);
+    for attr in inner {
+        visitor.push_attr(attr, krate.spans.inner_span);
+    }
+    visit::walk_crate(&mut visitor, krate);
+
+    visitor
+        .regions
+        .sort_by_key(|region| (region.range.start, region.range.end));
+    visitor.regions
+}
+
+struct CfgVisitor<'a> {
+    psess: &'a ParseSess,
+    regions: Vec<CfgRegion>,
+}
+
+impl CfgVisitor<'_> {
+    /// add a region for each outer cfg attribute of the node
+    fn push(&mut self, attrs: &[Attribute], span: Span) {
+        // the span of a node does not always include its attributes
+        let lo = attrs
+            .iter()
+            .filter(|attr| attr.style == AttrStyle::Outer)
+            .map(|attr| attr.span.lo())
+            .fold(span.lo(), std::cmp::min);
+        for attr in attrs.iter().filter(|attr| attr.style == AttrStyle::Outer) {
+            self.push_attr(attr, span.with_lo(lo));
+        }
+    }
+
+    fn push_attr(&mut self, attr: &Attribute, span: Span) {
+        if !attr.has_name(sym::cfg) || span.from_expansion() {
+            return;
+        }
+        let Some(list) = attr.meta_item_list() else {
+            return;
+        };
+        // predicates that cannot be parsed are reported by the compiler instead
+        let [meta] = list.as_slice() else {
+            return;
+        };
+        let (Some(cfg), Some(range)) = (parse_cfg(meta), super::span_range(self.psess, span))
+        else {
+            return;
+        };
+        let predicate = self
+            .psess
+            .source_map()
+            .span_to_snippet(meta.span())
+            .unwrap_or_default();
+
+        self.regions.push(CfgRegion {
+            range,
+            cfg,
+            predicate,
+        });
+    }
+}
+
+/// convert the predicate of a cfg attribute, such as `all(unix, feature = "gpu")`
+fn parse_cfg(meta: &MetaItemInner) -> Option<Cfg> {
+    let MetaItemInner::MetaItem(item) = meta else {
+        return None;
+    };
+    let name = item.ident()?.name;
+
+    match &item.kind {
+        MetaItemKind::Word => Some(Cfg::Option(name.to_string(), None)),
+        MetaItemKind::NameValue(lit) => match lit.kind {
+            LitKind::Str(value, _) => Some(Cfg::Option(name.to_string(), Some(value.to_string()))),
+            _ => None,
+        },
+        MetaItemKind::List(list) => {
+            let mut cfgs = list.iter().map(parse_cfg).collect::<Option<Vec<_>>>()?;
+            match name {
+                sym::all => Some(Cfg::All(cfgs)),
+                sym::any => Some(Cfg::Any(cfgs)),
+                sym::not if cfgs.len() == 1 => cfgs.pop().map(|cfg| Cfg::Not(Box::new(cfg))),
+                _ => None,
+            }
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for CfgVisitor<'_> {
+    fn visit_item(&mut self, i: &'ast Item) {
+        self.push(&i.attrs, i.span);
+        visit::walk_item(self, i);
+    }
+
+    fn visit_foreign_item(&mut self, i: &'ast ForeignItem) {
+        self.push(&i.attrs, i.span);
+        visit::walk_item(self, i);
+    }
+
+    fn visit_assoc_item(&mut self, i: &'ast AssocItem, ctxt: AssocCtxt) {
+        self.push(&i.attrs, i.span);
+        visit::walk_assoc_item(self, i, ctxt);
+    }
+
+    fn visit_variant(&mut self, v: &'ast Variant) {
+        self.push(&v.attrs, v.span);
+        visit::walk_variant(self, v);
+    }
+
+    fn visit_field_def(&mut self, s: &'ast FieldDef) {
+        self.push(&s.attrs, s.span);
+        visit::walk_field_def(self, s);
+    }
+
+    fn visit_local(&mut self, l: &'ast Local) {
+        self.push(&l.attrs, l.span);
+        visit::walk_local(self, l);
+    }
+
+    fn visit_expr(&mut self, ex: &'ast Expr) {
+        self.push(&ex.attrs, ex.span);
+        visit::walk_expr(self, ex);
+    }
+
+    fn visit_arm(&mut self, a: &'ast Arm) {
+        self.push(&a.attrs, a.span);
+        visit::walk_arm(self, a);
+    }
+
+    fn visit_expr_field(&mut self, f: &'ast ExprField) {
+        self.push(&f.attrs, f.span);
+        visit::walk_expr_field(self, f);
+    }
+
+    fn visit_pat_field(&mut self, fp: &'ast PatField) {
+        self.push(&fp.attrs, fp.span);
+        visit::walk_pat_field(self, fp);
+    }
+
+    fn visit_param(&mut self, param: &'ast Param) {
+        self.push(&param.attrs, param.span);
+        visit::walk_param(self, param);
+    }
+
+    fn visit_generic_param(&mut self, param: &'ast GenericParam) {
+        self.push(&param.attrs, param.ident.span);
+        visit::walk_generic_param(self, param);
+    }
+}
@@ -0,0 +1,63 @@
+//! collection of the syntax errors reported by the parser
+
+use std::sync::{Arc, Mutex};
+
+use super::{rustc_driver, rustc_error_messages, rustc_errors, rustc_span};
+
+use rustc_error_messages::{FluentBundle, LazyFallbackBundle};
+use rustc_errors::emitter::Emitter;
+use rustc_errors::registry::Registry;
+use rustc_errors::translation::{to_fluent_args, Translate};
+use rustc_errors::DiagInner;
+use rustc_span::source_map::SourceMap;
+use rustc_span::Span;
+
+/// messages and primary spans of the collected errors
+pub type Errors = Arc<Mutex<Vec<(String, Option<Span>)>>>;
+
+/// an emitter that stores the message and primary span of each error instead of printing it
+pub struct ErrorCollector {
+    fallback_bundle: LazyFallbackBundle,
+    errors: Errors,
+}
+
+impl ErrorCollector {
+    pub fn new(errors: Errors) -> Self {
+        Self {
+            fallback_bundle: rustc_errors::fallback_fluent_bundle(
+                rustc_driver::DEFAULT_LOCALE_RESOURCES.to_vec(),
+                false,
+            ),
+            errors,
+        }
+    }
+}
+
+impl Translate for ErrorCollector {
+    fn fluent_bundle(&self) -> Option<&FluentBundle> {
+        None
+    }
+
+    fn fallback_fluent_bundle(&self) -> &FluentBundle {
+        &self.fallback_bundle
+    }
+}
+
+impl Emitter for ErrorCollector {
+    fn source_map(&self) -> Option<&SourceMap> {
+        None
+    }
+
+    fn emit_diagnostic(&mut self, diag: DiagInner, _registry: &Registry) {
+        if !diag.is_error() {
+            return;
+        }
+
+        let args = to_fluent_args(diag.args.iter());
+        let message = self.translate_messages(&diag.messages, &args).into_owned();
+        self.errors
+            .lock()
+            .expect("poisoned")
+            .push((message, diag.span.primary_span()));
+    }
+}
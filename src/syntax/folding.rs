@@ -0,0 +1,161 @@
+//! folding ranges of items, match arms, imports, comments and regions
+
+use super::{rustc_ast, rustc_lexer, rustc_session, rustc_span};
+
+use rustc_ast::ptr::P;
+use rustc_ast::visit::{self, AssocCtxt, Visitor};
+use rustc_ast::{Arm, AssocItem, Crate, Item, ItemKind, ModKind};
+use rustc_lexer::TokenKind;
+use rustc_session::parse::ParseSess;
+use rustc_span::Span;
+use tower_lsp::lsp_types::{FoldingRange, FoldingRangeKind};
+
+/// folding ranges of the syntax tree
+pub fn syntax_ranges(psess: &ParseSess, krate: &Crate) -> Vec<FoldingRange> {
+    let mut visitor = FoldingVisitor {
+        psess,
+        ranges: Vec::new(),
+    };
+    visitor.push_use_groups(&krate.items);
+    visit::walk_crate(&mut visitor, krate);
+    visitor.ranges
+}
+
+/// folding ranges of consecutive line comments, block comments and `// region` markers, found
+/// with the lexer since comments are not part of the syntax tree
+#[expect(clippy::cast_possible_truncation)]
+pub fn comment_ranges(source: &str) -> Vec<FoldingRange> {
+    let mut ranges = Vec::new();
+    // start lines of the regions that are not closed yet
+    let mut regions = Vec::new();
+    // first and last line of the current run of line comments
+    let mut run: Option<(u32, u32)> = None;
+    let mut line = 0;
+    let mut offset = 0;
+
+    for token in rustc_lexer::tokenize(source) {
+        let text = &source[offset..offset + token.len as usize];
+        offset += token.len as usize;
+
+        match token.kind {
+            TokenKind::LineComment { .. } => {
+                let marker = text.trim_start_matches('/').trim_start();
+                if marker.starts_with("endregion") {
+                    push_run(&mut ranges, run.take());
+                    if let Some(start) = regions.pop() {
+                        ranges.push(folding_range(start, line, FoldingRangeKind::Region));
+                    }
+                } else if marker.starts_with("region") {
+                    push_run(&mut ranges, run.take());
+                    regions.push(line);
+                } else {
+                    run = match run {
+                        Some((start, end)) if end + 1 == line => Some((start, line)),
+                        _ => {
+                            push_run(&mut ranges, run);
+                            Some((line, line))
+                        }
+                    };
+                }
+            }
+            TokenKind::BlockComment { .. } => {
+                push_run(&mut ranges, run.take());
+                let end = line + text.matches('\n').count() as u32;
+                if end > line {
+                    ranges.push(folding_range(line, end, FoldingRangeKind::Comment));
+                }
+            }
+            TokenKind::Whitespace => {}
+            // code between comments ends the current run
+            _ => push_run(&mut ranges, run.take()),
+        }
+
+        line += text.matches('\n').count() as u32;
+    }
+    push_run(&mut ranges, run);
+
+    ranges
+}
+
+/// add the run of line comments if it spans multiple lines
+fn push_run(ranges: &mut Vec<FoldingRange>, run: Option<(u32, u32)>) {
+    if let Some((start, end)) = run.filter(|(start, end)| start < end) {
+        ranges.push(folding_range(start, end, FoldingRangeKind::Comment));
+    }
+}
+
+fn folding_range(start_line: u32, end_line: u32, kind: FoldingRangeKind) -> FoldingRange {
+    FoldingRange {
+        start_line,
+        start_character: None,
+        end_line,
+        end_character: None,
+        kind: Some(kind),
+        collapsed_text: None,
+    }
+}
+
+struct FoldingVisitor<'a> {
+    psess: &'a ParseSess,
+    ranges: Vec<FoldingRange>,
+}
+
+impl FoldingVisitor<'_> {
+    /// add the span if it spans multiple lines
+    fn push(&mut self, span: Span, kind: Option<FoldingRangeKind>) {
+        if span.from_expansion() {
+            return;
+        }
+        let Some(range) = super::span_range(self.psess, span) else {
+            return;
+        };
+        if range.start.line < range.end.line {
+            self.ranges.push(FoldingRange {
+                start_line: range.start.line,
+                start_character: None,
+                end_line: range.end.line,
+                end_character: None,
+                kind,
+                collapsed_text: None,
+            });
+        }
+    }
+
+    /// add each group of consecutive `use` items of a module
+    fn push_use_groups(&mut self, items: &[P<Item>]) {
+        let is_use = |item: &P<Item>| matches!(item.kind, ItemKind::Use(..));
+        for group in items.chunk_by(|a, b| is_use(a) && is_use(b)) {
+            if let (Some(first), Some(last)) = (group.first(), group.last()) {
+                if is_use(first) {
+                    self.push(first.span.to(last.span), Some(FoldingRangeKind::Imports));
+                }
+            }
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for FoldingVisitor<'_> {
+    fn visit_item(&mut self, i: &'ast Item) {
+        match &i.kind {
+            // imports are folded as groups
+            ItemKind::Use(..) => {}
+            ItemKind::Mod(_, ModKind::Loaded(items, ..)) => {
+                self.push(i.span, None);
+                self.push_use_groups(items);
+            }
+            _ => self.push(i.span, None),
+        }
+
+        visit::walk_item(self, i);
+    }
+
+    fn visit_assoc_item(&mut self, i: &'ast AssocItem, ctxt: AssocCtxt) {
+        self.push(i.span, None);
+        visit::walk_assoc_item(self, i, ctxt);
+    }
+
+    fn visit_arm(&mut self, a: &'ast Arm) {
+        self.push(a.span, None);
+        visit::walk_arm(self, a);
+    }
+}
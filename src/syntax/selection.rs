@@ -0,0 +1,166 @@
+//! selection ranges that expand along the syntax tree
+
+use super::{rustc_ast, rustc_session, rustc_span};
+
+use rustc_ast::visit::{self, AssocCtxt, Visitor};
+use rustc_ast::{
+    Arm, AssocItem, Attribute, Block, Crate, Expr, ExprField, FieldDef, ForeignItem, GenericParam,
+    Generics, Item, Param, Pat, PatField, Stmt, Ty, Variant,
+};
+use rustc_session::parse::ParseSess;
+use rustc_span::symbol::Ident;
+use rustc_span::Span;
+use tower_lsp::lsp_types::{Position, Range, SelectionRange};
+
+/// ranges of all syntax nodes, including the whole file
+pub fn syntax_ranges(psess: &ParseSess, krate: &Crate) -> Vec<Range> {
+    let mut visitor = SelectionVisitor {
+        psess,
+        ranges: Vec::new(),
+    };
+    visitor.push(krate.spans.inner_span);
+    visit::walk_crate(&mut visitor, krate);
+    visitor.ranges
+}
+
+/// nest the ranges containing the position from the outermost to the innermost
+pub fn selection_range(ranges: &[Range], position: Position) -> SelectionRange {
+    let mut containing = ranges
+        .iter()
+        .filter(|range| range.start <= position && position <= range.end)
+        .copied()
+        .collect::<Vec<_>>();
+    containing.sort_unstable_by(|a, b| (a.start, b.end).cmp(&(b.start, a.end)));
+    containing.dedup();
+
+    let mut selection: Option<SelectionRange> = None;
+    for range in containing {
+        // ranges that are not nested in the current one, such as those of overlapping nodes, are
+        // skipped
+        if selection
+            .as_ref()
+            .is_some_and(|parent| range.start < parent.range.start || parent.range.end < range.end)
+        {
+            continue;
+        }
+        selection = Some(SelectionRange {
+            range,
+            parent: selection.map(Box::new),
+        });
+    }
+
+    selection.unwrap_or(SelectionRange {
+        range: Range {
+            start: position,
+            end: position,
+        },
+        parent: None,
+    })
+}
+
+struct SelectionVisitor<'a> {
+    psess: &'a ParseSess,
+    ranges: Vec<Range>,
+}
+
+impl SelectionVisitor<'_> {
+    fn push(&mut self, span: Span) {
+        if span.from_expansion() {
+            return;
+        }
+        if let Some(range) = super::span_range(self.psess, span) {
+            self.ranges.push(range);
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for SelectionVisitor<'_> {
+    fn visit_ident(&mut self, ident: &'ast Ident) {
+        self.push(ident.span);
+    }
+
+    fn visit_item(&mut self, i: &'ast Item) {
+        self.push(i.span);
+        visit::walk_item(self, i);
+    }
+
+    fn visit_foreign_item(&mut self, i: &'ast ForeignItem) {
+        self.push(i.span);
+        visit::walk_item(self, i);
+    }
+
+    fn visit_assoc_item(&mut self, i: &'ast AssocItem, ctxt: AssocCtxt) {
+        self.push(i.span);
+        visit::walk_assoc_item(self, i, ctxt);
+    }
+
+    fn visit_block(&mut self, b: &'ast Block) {
+        self.push(b.span);
+        visit::walk_block(self, b);
+    }
+
+    fn visit_stmt(&mut self, s: &'ast Stmt) {
+        self.push(s.span);
+        visit::walk_stmt(self, s);
+    }
+
+    fn visit_param(&mut self, param: &'ast Param) {
+        self.push(param.span);
+        visit::walk_param(self, param);
+    }
+
+    fn visit_arm(&mut self, a: &'ast Arm) {
+        self.push(a.span);
+        visit::walk_arm(self, a);
+    }
+
+    fn visit_pat(&mut self, p: &'ast Pat) {
+        self.push(p.span);
+        visit::walk_pat(self, p);
+    }
+
+    fn visit_expr(&mut self, ex: &'ast Expr) {
+        self.push(ex.span);
+        visit::walk_expr(self, ex);
+    }
+
+    fn visit_ty(&mut self, t: &'ast Ty) {
+        self.push(t.span);
+        visit::walk_ty(self, t);
+    }
+
+    fn visit_generic_param(&mut self, param: &'ast GenericParam) {
+        self.push(param.span());
+        visit::walk_generic_param(self, param);
+    }
+
+    fn visit_generics(&mut self, g: &'ast Generics) {
+        self.push(g.span);
+        visit::walk_generics(self, g);
+    }
+
+    fn visit_field_def(&mut self, s: &'ast FieldDef) {
+        self.push(s.span);
+        visit::walk_field_def(self, s);
+    }
+
+    fn visit_variant(&mut self, v: &'ast Variant) {
+        self.push(v.span);
+        visit::walk_variant(self, v);
+    }
+
+    fn visit_expr_field(&mut self, f: &'ast ExprField) {
+        self.push(f.span);
+        visit::walk_expr_field(self, f);
+    }
+
+    fn visit_pat_field(&mut self, fp: &'ast PatField) {
+        self.push(fp.span);
+        visit::walk_pat_field(self, fp);
+    }
+
+    fn visit_attribute(&mut self, attr: &'ast Attribute) {
+        self.push(attr.span);
+        visit::walk_attribute(self, attr);
+    }
+}